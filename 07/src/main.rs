@@ -1,109 +1,359 @@
-use std::collections::HashMap;
+use std::io::BufRead;
 
 use nom::{
-    bytes::complete::tag,
-    character::complete::{line_ending, one_of},
+    character::{
+        complete,
+        streaming::{char, multispace0, one_of},
+    },
     combinator::{map_res, recognize},
-    multi::{many0, many1, separated_list0},
-    sequence::terminated,
+    multi::{many0, many1},
+    sequence::{preceded, terminated},
     IResult,
 };
 
 fn main() {
-    let input = std::fs::read_to_string(std::env::args().last().unwrap()).unwrap();
+    let file = std::fs::File::open(std::env::args().next_back().unwrap()).unwrap();
     let mut parser = Parser::default();
-    parser.parse(&input);
-    let list = parser.list.clone();
+    if let Err(err) = parser.read(std::io::BufReader::new(file)) {
+        eprintln!("{}", err);
+        std::process::exit(1);
+    }
     let hist = parser.position_hist();
-    let mut results: Vec<usize> = hist.iter().map(|elem| score(&hist, *elem)).collect();
 
-    let mut minval = usize::MAX;
-    let mut minval_g = usize::MAX;
-    for i in 0..(parser.max + 1) {
-        minval = std::cmp::min(minval, naive_score(&list, i));
-        minval_g = std::cmp::min(minval_g, naive_gaussian_score(&list, i));
-    }
+    println!("{}", cheapest(hist, score));
+    println!("{}", cheapest(hist, gaussian_score));
+}
 
-    println!("{}", minval);
-    println!("{}", minval_g);
+/// Lowest score of all positions, an empty list needs no fuel at all.
+fn cheapest(hist: &[usize], score: fn(&[usize], usize) -> u128) -> u128 {
+    (0..hist.len())
+        .map(|pos| score(hist, pos))
+        .min()
+        .unwrap_or(0)
 }
 
-fn score(hist: &[usize], pos: usize) -> usize {
-    let mut score: usize = 0;
+/// Fuel for all crabs to move to `pos`. Scores are summed in u128, as a distance is at most
+/// [MAX_POSITION] and there are less than 2^64 crabs, so neither score can overflow.
+fn score(hist: &[usize], pos: usize) -> u128 {
+    let mut score: u128 = 0;
     for (cur_pos, elem) in hist.iter().enumerate() {
-        score += *elem * abs_diff(cur_pos, pos);
+        score += *elem as u128 * cur_pos.abs_diff(pos) as u128;
     }
     score
 }
 
-fn abs_diff(val1: usize, val2: usize) -> usize {
-    if val1 > val2 {
-        val1 - val2
-    } else {
-        val2 - val1
-    }
+fn gaussian(val1: usize) -> u128 {
+    let val1 = val1 as u128;
+    ((val1 * val1) + val1) / 2
 }
 
-fn gaussian(val1: usize) -> usize {
-    ((val1 * val1) + val1) / 2
+fn gaussian_score(hist: &[usize], goal: usize) -> u128 {
+    hist.iter().enumerate().fold(0, |acc, (pos, elem)| {
+        acc + *elem as u128 * gaussian(pos.abs_diff(goal))
+    })
+}
+
+/// Largest accepted position. The histogram is dense, so it takes a slot for every position up to
+/// the largest one found.
+const MAX_POSITION: usize = 1 << 24;
+
+#[derive(Debug)]
+enum ParseError {
+    Io(std::io::Error),
+    /// Something else than the expected token has been found at the given byte offset.
+    Unexpected {
+        offset: usize,
+        expected: &'static str,
+        found: Option<u8>,
+    },
+    /// The position at the given byte offset does not fit into an usize.
+    Overflow {
+        offset: usize,
+    },
+    /// The position at the given byte offset is larger than [MAX_POSITION].
+    OutOfRange {
+        offset: usize,
+        position: usize,
+    },
+}
+
+impl std::fmt::Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ParseError::Io(err) => write!(f, "IO error: {}", err),
+            ParseError::Unexpected {
+                offset,
+                expected,
+                found: Some(found),
+            } => write!(
+                f,
+                "byte {}: expected {}, found {:?}",
+                offset,
+                expected,
+                char::from(*found)
+            ),
+            ParseError::Unexpected {
+                offset,
+                expected,
+                found: None,
+            } => write!(
+                f,
+                "byte {}: expected {}, found end of input",
+                offset, expected
+            ),
+            ParseError::Overflow { offset } => {
+                write!(f, "byte {}: position does not fit into usize", offset)
+            }
+            ParseError::OutOfRange { offset, position } => write!(
+                f,
+                "byte {}: position {} is larger than {}",
+                offset, position, MAX_POSITION
+            ),
+        }
+    }
 }
 
-fn naive_score(list: &[usize], goal: usize) -> usize {
-    list.iter().fold(0, |acc, elem| acc + abs_diff(*elem, goal))
+impl std::error::Error for ParseError {}
+
+impl From<std::io::Error> for ParseError {
+    fn from(err: std::io::Error) -> Self {
+        ParseError::Io(err)
+    }
 }
 
-fn naive_gaussian_score(list: &[usize], goal: usize) -> usize {
-    list.iter()
-        .fold(0, |acc, elem| acc + gaussian(abs_diff(*elem, goal)))
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+enum Expect {
+    #[default]
+    Position,
+    Separator,
 }
 
+/// Incremental parser for comma separated position lists.
+///
+/// Input can be fed in arbitrary chunks, only the not yet consumed tail of the last chunk is kept.
+/// Each position is directly counted in the histogram, so the list itself is never stored.
+#[derive(Debug, Default)]
 struct Parser {
     max: usize,
-    list: Vec<usize>,
+    count: usize,
+    hist: Vec<usize>,
+    buffer: Vec<u8>,
+    /// byte offset of the start of the buffer in the whole input
+    offset: usize,
+    expect: Expect,
 }
 
-impl Default for Parser {
-    fn default() -> Self {
-        Self {
-            max: 0,
-            list: Vec::new(),
+impl Parser {
+    fn decimal(input: &[u8]) -> IResult<&[u8], &[u8]> {
+        recognize(many1(terminated(one_of("0123456789"), many0(char('_')))))(input)
+    }
+
+    /// Parses a decimal, which is [None] if it does not fit into an usize.
+    fn decimal_value(input: &[u8]) -> IResult<&[u8], Option<usize>> {
+        map_res(Self::decimal, |val: &[u8]| {
+            String::from_utf8(val.iter().filter(|c| **c != b'_').cloned().collect())
+                .map(|val| val.parse::<usize>().ok())
+        })(input)
+    }
+
+    fn position(&mut self, offset: usize, val: Option<usize>) -> Result<(), ParseError> {
+        let val = val.ok_or(ParseError::Overflow { offset })?;
+        if val > MAX_POSITION {
+            return Err(ParseError::OutOfRange {
+                offset,
+                position: val,
+            });
+        }
+        self.max = std::cmp::max(self.max, val);
+        if self.hist.len() <= val {
+            self.hist.resize(val + 1, 0);
+        }
+        self.hist[val] += 1;
+        self.count += 1;
+        Ok(())
+    }
+
+    fn unexpected(&self, input: &[u8], expected: &'static str) -> ParseError {
+        let input = complete::multispace0::<_, ()>(input).unwrap().0;
+        ParseError::Unexpected {
+            offset: self.offset + self.buffer.len() - input.len(),
+            expected,
+            found: input.first().cloned(),
         }
     }
-}
 
-impl<'a> Parser {
-    fn decimal(input: &str) -> IResult<&str, &str> {
-        recognize(many1(terminated(
-            one_of("0123456789"),
-            many0(nom::character::complete::char('_')),
-        )))(input)
+    /// Consumes as many tokens of the buffer as possible without reaching its end.
+    fn consume(&mut self) -> Result<(), ParseError> {
+        let mut pos = 0;
+        loop {
+            let input = &self.buffer[pos..];
+            match self.expect {
+                Expect::Position => {
+                    let result = preceded(multispace0, Self::decimal_value)(input)
+                        .map(|(rest, val)| (self.buffer.len() - rest.len(), val));
+                    match result {
+                        Ok((consumed, val)) => {
+                            let start = self.buffer.len()
+                                - complete::multispace0::<_, ()>(input).unwrap().0.len();
+                            self.position(self.offset + start, val)?;
+                            pos = consumed;
+                            self.expect = Expect::Separator;
+                        }
+                        Err(nom::Err::Incomplete(_)) => break,
+                        Err(_) => return Err(self.unexpected(input, "a position")),
+                    }
+                }
+                Expect::Separator => match preceded(multispace0, char::<_, ()>(','))(input) {
+                    Ok((rest, _)) => {
+                        pos = self.buffer.len() - rest.len();
+                        self.expect = Expect::Position;
+                    }
+                    Err(nom::Err::Incomplete(_)) => break,
+                    Err(_) => return Err(self.unexpected(input, "','")),
+                },
+            }
+        }
+        self.buffer.drain(..pos);
+        self.offset += pos;
+        Ok(())
     }
 
-    fn decimal_value(input: &str) -> IResult<&str, usize> {
-        map_res(Self::decimal, |val: &str| val.parse::<usize>())(input)
+    /// Feeds the next chunk of the input to the parser.
+    fn feed(&mut self, chunk: &[u8]) -> Result<(), ParseError> {
+        self.buffer.extend_from_slice(chunk);
+        self.consume()
     }
 
-    fn position(&'a mut self) -> impl FnMut(&'a str) -> IResult<&str, ()> {
-        |input| {
-            let (input, val) = Self::decimal_value(input).unwrap();
-            self.max = std::cmp::max(self.max, val);
-            self.list.push(val);
-            Ok((input, {}))
+    /// Parses whatever is left in the buffer, as no more input will follow.
+    fn finish(&mut self) -> Result<(), ParseError> {
+        // the input has ended, so a trailing newline terminates a pending position
+        self.feed(b"\n")?;
+        let rest = complete::multispace0::<_, ()>(&self.buffer[..]).unwrap().0;
+        match (rest.first(), self.expect) {
+            (None, Expect::Position) if self.count > 0 => Err(ParseError::Unexpected {
+                offset: self.offset + self.buffer.len() - 1,
+                expected: "a position",
+                found: None,
+            }),
+            (None, _) => Ok(()),
+            (Some(_), _) => Err(self.unexpected(rest, "','")),
         }
     }
 
-    fn parse(&'a mut self, input: &str) {
-        let _ = separated_list0(tag(","), self.position())(input);
+    /// Parses the whole content of the reader chunk by chunk.
+    fn read(&mut self, mut reader: impl BufRead) -> Result<(), ParseError> {
+        loop {
+            let chunk = reader.fill_buf()?;
+            if chunk.is_empty() {
+                break;
+            }
+            let len = chunk.len();
+            self.feed(chunk)?;
+            reader.consume(len);
+        }
+        self.finish()
+    }
+
+    fn position_hist(&self) -> &[usize] {
+        &self.hist
     }
 }
 
-impl Parser {
-    fn position_hist(&self) -> Vec<usize> {
-        let mut res: Vec<usize> = vec![0; self.max + 1];
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn parse(input: &[u8]) -> Result<Parser, ParseError> {
+        let mut parser = Parser::default();
+        parser.read(input)?;
+        Ok(parser)
+    }
+
+    #[test]
+    fn example() {
+        let parser = parse(b"16,1,2,0,4,2,7,1,2,14\n").unwrap();
+        assert_eq!(parser.max, 16);
+        assert_eq!(parser.count, 10);
+        let hist = parser.position_hist();
+        assert_eq!(cheapest(hist, score), 37);
+        assert_eq!(cheapest(hist, gaussian_score), 168);
+    }
+
+    #[test]
+    fn large_scores() {
+        assert_eq!(cheapest(parse(b"").unwrap().position_hist(), score), 0);
+        assert_eq!(
+            cheapest(parse(b"").unwrap().position_hist(), gaussian_score),
+            0
+        );
+        // the fuel of many crabs at the largest position exceeds 64 bits
+        let mut input = b"16777216,".repeat(200_000);
+        input.extend_from_slice(b"0\n");
+        let parser = parse(&input).unwrap();
+        let hist = parser.position_hist();
+        let max = MAX_POSITION as u128;
+        assert_eq!(score(hist, 0), 200_000 * max);
+        assert_eq!(gaussian_score(hist, 0), 200_000 * (max * (max + 1) / 2));
+        assert!(gaussian_score(hist, 0) > u64::MAX as u128);
+    }
+
+    #[test]
+    fn chunked() {
+        let input = b" 16 ,1,\n2,0,4 ,2,7,1_0,2,14\r\n\n";
+        let whole = parse(input).unwrap();
+        for size in 1..input.len() {
+            let mut parser = Parser::default();
+            input
+                .chunks(size)
+                .for_each(|chunk| parser.feed(chunk).unwrap());
+            parser.finish().unwrap();
+            assert_eq!(parser.position_hist(), whole.position_hist());
+            assert_eq!(parser.count, 10);
+        }
+    }
 
-        self.list.iter().for_each(|elem| {
-            res[*elem] += 1;
-        });
-        res
+    #[test]
+    fn errors() {
+        assert!(parse(b"").unwrap().hist.is_empty());
+        assert!(matches!(
+            parse(b"1,2,\n"),
+            Err(ParseError::Unexpected {
+                offset: 5,
+                found: None,
+                ..
+            })
+        ));
+        assert!(matches!(
+            parse(b"1,2;3"),
+            Err(ParseError::Unexpected {
+                offset: 3,
+                found: Some(b';'),
+                ..
+            })
+        ));
+        assert!(matches!(
+            parse(b"1, x"),
+            Err(ParseError::Unexpected {
+                offset: 3,
+                found: Some(b'x'),
+                ..
+            })
+        ));
+        assert!(matches!(
+            parse(b"1,99999999999999999999999"),
+            Err(ParseError::Overflow { offset: 2 })
+        ));
+        assert!(matches!(
+            parse(b"1,18446744073709551615"),
+            Err(ParseError::OutOfRange {
+                offset: 2,
+                position: usize::MAX
+            })
+        ));
+        assert!(matches!(
+            parse(b"1000000000000"),
+            Err(ParseError::OutOfRange { offset: 0, .. })
+        ));
+        assert_eq!(parse(b"16777216").unwrap().max, MAX_POSITION);
     }
 }
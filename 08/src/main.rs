@@ -1,75 +1,220 @@
 use nom::{
     bytes::complete::tag,
-    character::complete::{line_ending, one_of, space0, space1},
-    combinator::{map_res, recognize},
-    complete::take,
-    multi::{many0, many1, separated_list1},
-    sequence::{self, preceded, terminated},
-    IResult,
+    character::complete::{anychar, line_ending, none_of, satisfy, space0, space1},
+    combinator::all_consuming,
+    multi::many1,
+    sequence::{preceded, separated_pair, terminated},
+    Finish, IResult,
 };
 
-use std::collections::{HashMap, HashSet};
-
 fn main() {
-    let input = std::fs::read_to_string(std::env::args().last().unwrap()).unwrap();
-    let mut sequences = parse(&input);
+    let mut args: Vec<String> = std::env::args().skip(1).collect();
+    let input = std::fs::read_to_string(args.pop().unwrap()).unwrap();
+    let scrambled = args.iter().any(|arg| arg == "--scrambled");
+    let render = scrambled || args.iter().any(|arg| arg == "--render");
+    let custom = args.iter().position(|arg| arg == "--glyphs");
+    let table = match custom {
+        Some(pos) => {
+            let file = args.get(pos + 1).unwrap_or_else(|| {
+                eprintln!("usage: --glyphs <definition> <input>");
+                std::process::exit(1);
+            });
+            let definition = std::fs::read_to_string(file).unwrap();
+            GlyphTable::parse(&definition).unwrap_or_else(|err| {
+                eprintln!("invalid glyph definition: {}", err);
+                std::process::exit(1);
+            })
+        }
+        None => GlyphTable::seven_segment(),
+    };
+    if render && table != GlyphTable::seven_segment() {
+        eprintln!("rendering is only supported for the built-in seven-segment display");
+        std::process::exit(1);
+    }
+    let sequences = parse(&input, &table);
 
     println!(
         "{}",
         sequences
             .iter()
-            .fold(0, |acc, elem| { acc + elem.naive_number_count() })
-    );
-    println!(
-        "{}",
-        sequences.iter_mut().fold(0, |acc, elem| {
-            elem.fill_naive_numbers();
-            elem.find_based_on_naive();
-            elem.output() + acc
-        })
+            .fold(0, |acc, elem| { acc + elem.naive_number_count(&table) })
     );
+    let mut sum = 0;
+    sequences.iter().enumerate().for_each(|(entry, elem)| {
+        let solution = elem.solve(&table);
+        match &solution {
+            Solution::Unique(wiring) => {
+                if custom.is_none() {
+                    sum += elem.output(&table, wiring);
+                }
+                if custom.is_some() || render {
+                    println!("entry {}: {}", entry + 1, elem.decode(&table, wiring));
+                }
+            }
+            solution => eprintln!("entry {}: {}", entry + 1, solution.display(&table)),
+        }
+        if render {
+            elem.render(&table, solution.wiring(), scrambled)
+                .iter()
+                .for_each(|row| println!("{}", row));
+        }
+    });
+    if custom.is_none() {
+        println!("{}", sum);
+    }
 }
 
+/// The seven-segment digits, with the segments named as in the AoC description.
+const SEVEN_SEGMENT: &str = "segments: abcdefg
+0: abcefg
+1: cf
+2: acdeg
+3: acdfg
+4: bcdf
+5: abdfg
+6: abdefg
+7: acf
+8: abcdefg
+9: abcdfg
+";
+
+/// Seven-segment layout of a glyph, each cell names the segment (by its number) which lights it.
+/// The numbers follow the segment order of [SEVEN_SEGMENT], so only that table can be rendered.
+const LAYOUT: [&str; 7] = [
+    " 0000 ", "1    2", "1    2", " 3333 ", "4    5", "4    5", " 6666 ",
+];
+
+/// A segment, identified by its position in the alphabet of a [GlyphTable].
 #[derive(Copy, Clone, Debug, Eq, PartialEq, Hash, PartialOrd, Ord)]
-enum Segment {
-    A = 0,
-    B = 1,
-    C = 2,
-    D = 3,
-    E = 4,
-    F = 5,
-    G = 6,
-}
+struct Segment(usize);
 
 impl Segment {
     fn as_num(&self) -> usize {
-        *self as usize
+        self.0
     }
 
     fn from_num(num: usize) -> Self {
-        match num {
-            0 => Segment::A,
-            1 => Segment::B,
-            2 => Segment::C,
-            3 => Segment::D,
-            4 => Segment::E,
-            5 => Segment::F,
-            6 => Segment::G,
-            _ => unreachable!(),
-        }
+        Segment(num)
     }
+}
 
-    fn new_segment(input: &str) -> Self {
-        match input {
-            "a" => Segment::A,
-            "b" => Segment::B,
-            "c" => Segment::C,
-            "d" => Segment::D,
-            "e" => Segment::E,
-            "f" => Segment::F,
-            "g" => Segment::G,
-            _ => unreachable!(),
+/// The segment alphabet of a display together with the glyphs it can show.
+#[derive(Clone, Debug, Eq, PartialEq)]
+struct GlyphTable {
+    segments: Vec<char>,
+    /// Each glyph with the mask of its lit segments, where bit `i` represents the segment with number `i`.
+    glyphs: Vec<(char, u32)>,
+}
+
+impl GlyphTable {
+    fn seven_segment() -> Self {
+        Self::parse(SEVEN_SEGMENT).unwrap()
+    }
+
+    /// Parses a definition consisting of a `segments: <alphabet>` line, followed by one
+    /// `<glyph>: <lit segments>` line per glyph.
+    fn parse(input: &str) -> Result<Self, String> {
+        fn alphabet(input: &str) -> IResult<&str, Vec<char>> {
+            preceded(
+                terminated(tag("segments:"), space0),
+                terminated(many1(none_of(" \t\r:")), space0),
+            )(input)
+        }
+        fn glyph(input: &str) -> IResult<&str, (char, Vec<char>)> {
+            terminated(
+                separated_pair(anychar, tag(":"), preceded(space0, many1(none_of(" \t\r")))),
+                space0,
+            )(input)
+        }
+        let malformed = |line: usize| format!("line {}: malformed definition", line + 1);
+        let mut lines = input.lines().enumerate();
+        let (_, segments) = lines
+            .next()
+            .and_then(|(_, line)| all_consuming(alphabet)(line).finish().ok())
+            .ok_or_else(|| malformed(0))?;
+        let glyphs = lines
+            .filter(|(_, line)| !line.trim().is_empty())
+            .map(|(nr, line)| {
+                all_consuming(glyph)(line)
+                    .finish()
+                    .map(|(_, glyph)| glyph)
+                    .map_err(|_| malformed(nr))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        if segments.len() > 32 {
+            return Err(format!(
+                "{} segments, at most 32 are supported",
+                segments.len()
+            ));
+        }
+        if let Some(dup) = segments
+            .iter()
+            .enumerate()
+            .find(|(pos, elem)| segments[..*pos].contains(elem))
+        {
+            return Err(format!("segment '{}' is defined twice", dup.1));
         }
+        let mut table = Self {
+            segments,
+            glyphs: Vec::new(),
+        };
+        for (glyph, lit) in glyphs {
+            let mask = lit.iter().try_fold(0, |acc, elem| {
+                table
+                    .segment(*elem)
+                    .map(|seg| acc | (1 << seg.as_num()))
+                    .ok_or(format!(
+                        "glyph '{}' uses the unknown segment '{}'",
+                        glyph, elem
+                    ))
+            })?;
+            if table.glyphs.iter().any(|(other, _)| *other == glyph) {
+                return Err(format!("glyph '{}' is defined twice", glyph));
+            }
+            if let Some(other) = table.glyph(mask) {
+                return Err(format!(
+                    "glyphs '{}' and '{}' light the same segments",
+                    other, glyph
+                ));
+            }
+            table.glyphs.push((glyph, mask));
+        }
+        Ok(table)
+    }
+
+    fn len(&self) -> usize {
+        self.segments.len()
+    }
+
+    fn all_segments(&self) -> u32 {
+        u32::MAX >> (32 - self.len())
+    }
+
+    fn segment(&self, symbol: char) -> Option<Segment> {
+        self.segments
+            .iter()
+            .position(|elem| *elem == symbol)
+            .map(Segment::from_num)
+    }
+
+    fn symbol(&self, segment: Segment) -> char {
+        self.segments[segment.as_num()]
+    }
+
+    fn glyph(&self, mask: u32) -> Option<char> {
+        self.glyphs
+            .iter()
+            .find(|(_, elem)| *elem == mask)
+            .map(|(glyph, _)| *glyph)
+    }
+
+    /// Masks of all glyphs which light the given number of segments.
+    fn with_count(&self, count: u32) -> impl Iterator<Item = u32> + '_ {
+        self.glyphs
+            .iter()
+            .map(|(_, mask)| *mask)
+            .filter(move |mask| mask.count_ones() == count)
     }
 }
 
@@ -85,25 +230,73 @@ impl Digit {
         result
     }
 
-    fn naive_number(&self) -> Option<usize> {
-        match self.segments.len() {
-            2 => Some(1),
-            3 => Some(7),
-            4 => Some(4),
-            7 => Some(8),
+    /// The glyph, if it is the only one with this number of segments.
+    fn naive_number(&self, table: &GlyphTable) -> Option<char> {
+        let mut glyphs = table.with_count(self.segments.len() as u32);
+        match (glyphs.next(), glyphs.next()) {
+            (Some(mask), None) => table.glyph(mask),
             _ => None,
         }
     }
 
-    fn compareSegs(&self, other: &Self) -> usize {
-        let left: HashSet<Segment> = HashSet::from_iter(self.segments.iter().cloned());
-        let right: HashSet<Segment> = HashSet::from_iter(other.segments.iter().cloned());
+    /// Bitmask of the lit segments, where bit `i` represents the segment with number `i`.
+    fn mask(&self) -> u32 {
+        self.segments
+            .iter()
+            .fold(0, |acc, elem| acc | (1 << elem.as_num()))
+    }
+}
+
+/// Maximal number of fitting wirings which are enumerated for a single entry.
+const SEARCH_LIMIT: usize = 10_000;
 
-        left.intersection(&right).count()
+/// Maps each scrambled wire (by its segment number) to the segment it actually lights.
+#[derive(Clone, Debug, Eq, PartialEq)]
+struct Wiring {
+    mapping: Vec<Segment>,
+}
+
+impl Wiring {
+    fn translate(&self, digit: &Digit) -> u32 {
+        digit.segments.iter().fold(0, |acc, elem| {
+            acc | (1 << self.mapping[elem.as_num()].as_num())
+        })
     }
 
-    fn len(&self) -> usize {
-        self.segments.len()
+    fn number(&self, table: &GlyphTable, digit: &Digit) -> Option<char> {
+        table.glyph(self.translate(digit))
+    }
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+enum Solution {
+    Unique(Wiring),
+    /// The number of wirings which explain all observed patterns, capped at [SEARCH_LIMIT].
+    Ambiguous(usize),
+    Inconsistent,
+}
+
+impl Solution {
+    fn wiring(&self) -> Option<&Wiring> {
+        match self {
+            Solution::Unique(wiring) => Some(wiring),
+            _ => None,
+        }
+    }
+
+    fn display(&self, table: &GlyphTable) -> String {
+        match self {
+            Solution::Unique(wiring) => wiring
+                .mapping
+                .iter()
+                .map(|elem| table.symbol(*elem))
+                .collect(),
+            Solution::Ambiguous(SEARCH_LIMIT) => {
+                format!("ambiguous (at least {} wirings fit)", SEARCH_LIMIT)
+            }
+            Solution::Ambiguous(count) => format!("ambiguous ({} wirings fit)", count),
+            Solution::Inconsistent => "inconsistent (no wiring fits)".to_string(),
+        }
     }
 }
 
@@ -111,8 +304,6 @@ impl Digit {
 struct InputSequence {
     sequence: Vec<Digit>,
     outputindex: usize,
-    digit_to_nbr: Vec<Option<usize>>,
-    nbr_to_digit: Vec<Option<usize>>,
 }
 
 impl InputSequence {
@@ -123,123 +314,338 @@ impl InputSequence {
             .cloned()
             .chain(output.iter().cloned())
             .collect();
-        let len = sequence.len();
         Self {
             sequence,
             outputindex,
-            digit_to_nbr: vec![None; len],
-            nbr_to_digit: vec![None; len],
         }
     }
 
-    fn naive_number_count(&self) -> usize {
+    fn naive_number_count(&self, table: &GlyphTable) -> usize {
         self.sequence[self.outputindex..]
             .iter()
-            .fold(0, |acc, elem| match elem.naive_number() {
+            .fold(0, |acc, elem| match elem.naive_number(table) {
                 Some(_) => acc + 1,
                 None => acc,
             })
     }
 
-    fn output(&self) -> usize {
-        let mut result = String::new();
-
-        self.digit_to_nbr[self.outputindex..]
+    fn decode(&self, table: &GlyphTable, wiring: &Wiring) -> String {
+        self.sequence[self.outputindex..]
             .iter()
-            .for_each(|elem| {
-                result = format!("{}{}", result, elem.unwrap());
-            });
-        result.parse().unwrap()
+            .map(|elem| wiring.number(table, elem).unwrap())
+            .collect()
     }
 
-    fn fill_naive_numbers(&mut self) {
-        for i in 0..self.sequence.len() {
-            let naive_nmbr = self.sequence[i].naive_number();
-            self.digit_to_nbr[i] = naive_nmbr;
-            if naive_nmbr.is_some() {
-                self.nbr_to_digit[naive_nmbr.unwrap()] = Some(i);
-            }
+    fn output(&self, table: &GlyphTable, wiring: &Wiring) -> usize {
+        self.decode(table, wiring).parse().unwrap()
+    }
+
+    /// Draws the output digits as in the [LAYOUT], with each lit segment shown by its name.
+    /// The scrambled view shows the patterns as if every wire was connected to its own segment.
+    fn render(&self, table: &GlyphTable, wiring: Option<&Wiring>, scrambled: bool) -> Vec<String> {
+        let output = &self.sequence[self.outputindex..];
+        let mut views: Vec<Vec<u32>> = Vec::new();
+        if scrambled || wiring.is_none() {
+            views.push(output.iter().map(Digit::mask).collect());
+        }
+        if let Some(wiring) = wiring {
+            views.push(output.iter().map(|elem| wiring.translate(elem)).collect());
         }
+        LAYOUT
+            .iter()
+            .map(|row| {
+                views
+                    .iter()
+                    .map(|masks| {
+                        masks
+                            .iter()
+                            .map(|mask| {
+                                row.chars()
+                                    .map(|cell| match cell.to_digit(10) {
+                                        Some(seg) if mask & (1 << seg) != 0 => {
+                                            table.symbol(Segment::from_num(seg as usize))
+                                        }
+                                        Some(_) => '.',
+                                        None => ' ',
+                                    })
+                                    .collect::<String>()
+                            })
+                            .collect::<Vec<_>>()
+                            .join(" ")
+                    })
+                    .collect::<Vec<_>>()
+                    .join("  |  ")
+                    .trim_end()
+                    .to_string()
+            })
+            .collect()
     }
 
-    fn find_based_on_naive(&mut self) {
-        for i in 0..self.sequence.len() {
-            match self.sequence[i].len() {
-                5 => {
-                    if self.sequence[i].compareSegs(&self.sequence[self.nbr_to_digit[7].unwrap()])
-                        == 3
-                    {
-                        Some(3)
-                    } else if self.sequence[i]
-                        .compareSegs(&self.sequence[self.nbr_to_digit[4].unwrap()])
-                        == 3
-                    {
-                        Some(5)
+    /// Restricts the possible segments of each wire, based on the glyphs which have as many
+    /// segments as an observed pattern, until no further restriction is found.
+    fn propagate(&self, table: &GlyphTable) -> Vec<u32> {
+        let mut candidates = vec![table.all_segments(); table.len()];
+        let mut changed = true;
+        while changed {
+            let before = candidates.clone();
+            for digit in &self.sequence {
+                let pattern = digit.mask();
+                let (union, intersection) = table
+                    .with_count(pattern.count_ones())
+                    .fold((0, table.all_segments()), |(union, intersection), elem| {
+                        (union | elem, intersection & elem)
+                    });
+                for (wire, candidate) in candidates.iter_mut().enumerate() {
+                    if pattern & (1 << wire) != 0 {
+                        *candidate &= union;
                     } else {
-                        Some(2)
+                        *candidate &= !intersection;
                     }
                 }
-                6 => {
-                    if self.sequence[i].compareSegs(&self.sequence[self.nbr_to_digit[1].unwrap()])
-                        == 1
-                    {
-                        Some(6)
-                    } else if self.sequence[i]
-                        .compareSegs(&self.sequence[self.nbr_to_digit[4].unwrap()])
-                        == 4
-                    {
-                        Some(9)
-                    } else {
-                        Some(0)
-                    }
+            }
+            // a segment which is the only candidate of a wire can not be lit by any other wire
+            for wire in 0..table.len() {
+                if candidates[wire].count_ones() == 1 {
+                    let fixed = candidates[wire];
+                    candidates
+                        .iter_mut()
+                        .enumerate()
+                        .filter(|(other, _)| *other != wire)
+                        .for_each(|(_, candidate)| *candidate &= !fixed);
                 }
-                _ => None,
             }
-            .and_then(|value| {
-                self.digit_to_nbr[i] = Some(value);
-                self.nbr_to_digit[value] = Some(i);
-                Some(value)
-            });
+            changed = before != candidates;
+        }
+        candidates
+    }
+
+    /// Checks whether each pattern can still become a glyph with the wires assigned so far.
+    fn feasible(&self, table: &GlyphTable, wiring: &Wiring) -> bool {
+        self.sequence.iter().all(|digit| {
+            let pattern = digit.mask();
+            let (lit, unlit) =
+                wiring
+                    .mapping
+                    .iter()
+                    .enumerate()
+                    .fold((0, 0), |(lit, unlit), (wire, seg)| {
+                        if pattern & (1 << wire) != 0 {
+                            (lit | (1 << seg.as_num()), unlit)
+                        } else {
+                            (lit, unlit | (1 << seg.as_num()))
+                        }
+                    });
+            table
+                .with_count(pattern.count_ones())
+                .any(|glyph| glyph & lit == lit && glyph & unlit == 0)
+        })
+    }
+
+    fn search(
+        &self,
+        table: &GlyphTable,
+        candidates: &[u32],
+        wiring: &mut Wiring,
+        used: u32,
+        found: &mut Vec<Wiring>,
+    ) {
+        let wire = wiring.mapping.len();
+        if wire == table.len() {
+            found.push(wiring.clone());
+            return;
+        }
+        for seg in 0..table.len() {
+            if found.len() >= SEARCH_LIMIT {
+                return;
+            }
+            if candidates[wire] & !used & (1 << seg) != 0 {
+                wiring.mapping.push(Segment::from_num(seg));
+                if self.feasible(table, wiring) {
+                    self.search(table, candidates, wiring, used | (1 << seg), found);
+                }
+                wiring.mapping.pop();
+            }
+        }
+    }
+
+    /// Finds the wirings out of all permutations of the segments which turn each observed pattern
+    /// into a glyph of the table.
+    fn solve(&self, table: &GlyphTable) -> Solution {
+        let candidates = self.propagate(table);
+        let mut wiring = Wiring {
+            mapping: Vec::new(),
+        };
+        let mut wirings = Vec::new();
+        self.search(table, &candidates, &mut wiring, 0, &mut wirings);
+        match wirings.len() {
+            0 => Solution::Inconsistent,
+            1 => Solution::Unique(wirings.pop().unwrap()),
+            count => Solution::Ambiguous(count),
         }
     }
 }
 
-fn segment(input: &str) -> IResult<&str, Segment> {
-    let (input, symbol) = nom::branch::alt((
-        tag("a"),
-        tag("b"),
-        tag("c"),
-        tag("d"),
-        tag("e"),
-        tag("f"),
-        tag("g"),
-    ))(input)?;
-    Ok((input, Segment::new_segment(symbol)))
+fn segment(table: &GlyphTable) -> impl Fn(&str) -> IResult<&str, Segment> + '_ {
+    move |input| {
+        let (input, symbol) = satisfy(|elem| table.segment(elem).is_some())(input)?;
+        Ok((input, table.segment(symbol).unwrap()))
+    }
 }
 
-fn digit(input: &str) -> IResult<&str, Digit> {
-    let (input, digit) = terminated(many1(segment), space0)(input)?;
-    Ok((input, Digit::new(digit)))
+fn digit(table: &GlyphTable) -> impl Fn(&str) -> IResult<&str, Digit> + '_ {
+    move |input| {
+        let (input, digit) = terminated(many1(segment(table)), space0)(input)?;
+        Ok((input, Digit::new(digit)))
+    }
 }
 
-fn input_sequence(input: &str) -> IResult<&str, InputSequence> {
-    let (input, warmup) = many1(digit)(input)?;
-    let (input, output) = preceded(tag("| "), terminated(many1(digit), line_ending))(input)?;
-    Ok((input, InputSequence::new(warmup, output)))
+fn input_sequence(table: &GlyphTable) -> impl Fn(&str) -> IResult<&str, InputSequence> + '_ {
+    move |input| {
+        let (input, warmup) = many1(digit(table))(input)?;
+        let (input, output) = preceded(
+            terminated(tag("|"), space1),
+            terminated(many1(digit(table)), line_ending),
+        )(input)?;
+        Ok((input, InputSequence::new(warmup, output)))
+    }
 }
 
-fn parse(input: &str) -> Vec<InputSequence> {
-    many1(input_sequence)(input).unwrap().1
+fn parse(input: &str, table: &GlyphTable) -> Vec<InputSequence> {
+    many1(input_sequence(table))(input).unwrap().1
 }
 #[cfg(test)]
 mod test {
     use super::*;
-    use nom::IResult;
 
     #[test]
     fn test() {
-        let segments = vec![Segment::A, Segment::B, Segment::D];
+        let table = GlyphTable::seven_segment();
+        let segments = vec![Segment(0), Segment(1), Segment(3)];
+
+        assert_eq!(Digit { segments }, digit(&table)("abd").unwrap().1);
+    }
+
+    #[test]
+    fn solve() {
+        let table = GlyphTable::seven_segment();
+        let entry = parse(
+            "acedgfb cdfbe gcdfa fbcad dab cefabd cdfgeb eafb cagedb ab | cdfeb fcadb cdfeb cdbaf\n",
+            &table,
+        );
+        let wiring = match entry[0].solve(&table) {
+            Solution::Unique(wiring) => wiring,
+            solution => panic!("unexpected solution {:?}", solution),
+        };
+        assert_eq!(Solution::Unique(wiring.clone()).display(&table), "cfgabde");
+        assert_eq!(entry[0].output(&table, &wiring), 5353);
+    }
+
+    #[test]
+    fn solve_without_one_and_four() {
+        let table = GlyphTable::seven_segment();
+        let entry = parse(
+            "cdfbe gcdfa fbcad dab cefabd cdfgeb cagedb | cdfeb fcadb cdfeb cdbaf\n",
+            &table,
+        );
+        match entry[0].solve(&table) {
+            Solution::Unique(wiring) => assert_eq!(entry[0].output(&table, &wiring), 5353),
+            solution => panic!("unexpected solution {:?}", solution),
+        }
+    }
+
+    #[test]
+    fn ambiguous_and_inconsistent() {
+        let table = GlyphTable::seven_segment();
+        let entry = parse("ab | ab\n", &table);
+        assert!(matches!(entry[0].solve(&table), Solution::Ambiguous(240)));
+        let entry = parse("ab abc abcd | ab\n", &table);
+        assert_eq!(entry[0].solve(&table), Solution::Inconsistent);
+    }
 
-        assert_eq!(Digit { segments }, digit("abd").unwrap().1);
+    #[test]
+    fn glyph_table() {
+        let table = GlyphTable::seven_segment();
+        assert_eq!(table.len(), 7);
+        assert_eq!(table.glyph(0b0100100), Some('1'));
+        assert_eq!(
+            GlyphTable::parse("segments: abc\n1: ab\n2: ba\n"),
+            Err("glyphs '1' and '2' light the same segments".to_string())
+        );
+        assert_eq!(
+            GlyphTable::parse("segments: abc\n1: ab\n2: bd\n"),
+            Err("glyph '2' uses the unknown segment 'd'".to_string())
+        );
+        assert_eq!(
+            GlyphTable::parse("segments: abc\n1: ab\n2 bc\n"),
+            Err("line 3: malformed definition".to_string())
+        );
+    }
+
+    #[test]
+    fn alphanumeric_segments() {
+        for (file, len) in [("fourteen-segment.txt", 14), ("sixteen-segment.txt", 16)] {
+            let table = GlyphTable::parse(&std::fs::read_to_string(file).unwrap()).unwrap();
+            assert_eq!(table.len(), len);
+            assert_eq!(table.glyphs.len(), 36);
+            // scrambled by mirroring the alphabet, i.e. the first wire lights the last segment
+            let scramble = |glyphs: &str| {
+                glyphs
+                    .chars()
+                    .map(|glyph| {
+                        let mask = table
+                            .glyphs
+                            .iter()
+                            .find(|(elem, _)| *elem == glyph)
+                            .unwrap()
+                            .1;
+                        (0..len)
+                            .filter(|seg| mask & (1 << seg) != 0)
+                            .map(|seg| table.segments[len - 1 - seg])
+                            .collect::<String>()
+                    })
+                    .collect::<Vec<_>>()
+                    .join(" ")
+            };
+            let line = format!(
+                "{} | {}\n",
+                scramble("0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZ"),
+                scramble("C0DE")
+            );
+            let entry = parse(&line, &table);
+            match entry[0].solve(&table) {
+                Solution::Unique(wiring) => assert_eq!(entry[0].decode(&table, &wiring), "C0DE"),
+                solution => panic!("unexpected solution {}", solution.display(&table)),
+            }
+        }
+    }
+
+    #[test]
+    fn render() {
+        let table = GlyphTable::seven_segment();
+        let entry = parse(
+            "acedgfb cdfbe gcdfa fbcad dab cefabd cdfgeb eafb cagedb ab | cdfeb\n",
+            &table,
+        );
+        let solution = entry[0].solve(&table);
+        assert_eq!(
+            entry[0].render(&table, solution.wiring(), false),
+            vec![" aaaa", "b    .", "b    .", " dddd", ".    f", ".    f", " gggg"]
+        );
+        assert_eq!(
+            entry[0].render(&table, solution.wiring(), true),
+            vec![
+                " ....   |   aaaa",
+                "b    c  |  b    .",
+                "b    c  |  b    .",
+                " dddd   |   dddd",
+                "e    f  |  .    f",
+                "e    f  |  .    f",
+                " ....   |   gggg",
+            ]
+        );
+        assert_eq!(
+            entry[0].render(&table, None, false),
+            entry[0].render(&table, None, true)
+        );
     }
 }
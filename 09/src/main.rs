@@ -1,28 +1,98 @@
-use std::collections::HashSet;
+use std::collections::{BTreeSet, VecDeque};
 
 use nom::{
-    bytes::complete::{tag, take_while_m_n},
-    character::{
-        complete::{char, line_ending, one_of},
-        is_digit,
-    },
+    character::complete::{line_ending, one_of},
     combinator::{map_res, recognize},
-    multi::{many0, many1, separated_list0, separated_list1},
+    multi::{many1, separated_list1},
     sequence::terminated,
     IResult,
 };
 
 fn main() {
-    let input = std::fs::read_to_string(std::env::args().last().unwrap()).unwrap();
+    let mut args: Vec<String> = std::env::args().skip(1).collect();
+    let input = std::fs::read_to_string(args.pop().unwrap()).unwrap();
     let topography = parse(&input);
-    println!("{}", score_part1(&topography));
-    println!("{}", score_part2(&topography));
+    let drainage = args.iter().any(|arg| arg == "--drainage");
+    let basins = if drainage {
+        BasinMap::drainage(&topography)
+    } else {
+        BasinMap::walled(&topography)
+    };
+    if drainage {
+        println!("{}", basins.risk(&topography));
+    } else {
+        println!("{}", score_part1(&topography));
+    }
+    println!("{}", score_part2(&basins));
+    if args.iter().any(|arg| arg == "--minima") {
+        naive_minima(&topography).iter().for_each(|elem| {
+            println!(
+                "{} {}",
+                elem.display(&topography.dims),
+                elem.get_val(&topography)
+            )
+        });
+    }
+    if let Some(pos) = args.iter().position(|arg| arg == "--export") {
+        std::fs::write(&args[pos + 1], basins.export()).unwrap();
+    }
+}
+
+/// Extent of a heightmap, a 2D map consists of a single layer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Dimensions {
+    width: usize,
+    height: usize,
+    depth: usize,
+}
+
+impl Dimensions {
+    fn len(&self) -> usize {
+        self.width * self.height * self.depth
+    }
+
+    fn index(&self, point: &Point) -> usize {
+        (point.z * self.height + point.y) * self.width + point.x
+    }
+
+    /// All points in reading order, i.e. layer by layer, each from top to bottom and left to right.
+    fn points(&self) -> impl Iterator<Item = Point> + '_ {
+        (0..self.depth).flat_map(move |z| {
+            (0..self.height).flat_map(move |y| (0..self.width).map(move |x| Point { x, y, z }))
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Heightmap {
+    dims: Dimensions,
+    values: Vec<usize>,
+}
+
+impl Heightmap {
+    fn new(layers: Vec<Vec<Vec<usize>>>) -> Self {
+        let dims = Dimensions {
+            width: layers[0][0].len(),
+            height: layers[0].len(),
+            depth: layers.len(),
+        };
+        assert!(
+            layers.iter().all(|layer| layer.len() == dims.height
+                && layer.iter().all(|line| line.len() == dims.width)),
+            "all layers need to have the same size"
+        );
+        Self {
+            dims,
+            values: layers.into_iter().flatten().flatten().collect(),
+        }
+    }
 }
 
 #[derive(Debug, Clone, Copy, Hash, PartialEq, Eq, PartialOrd, Ord)]
 struct Point {
     x: usize,
     y: usize,
+    z: usize,
 }
 
 impl Point {
@@ -30,18 +100,18 @@ impl Point {
         if self.x > 0 {
             Some(Point {
                 x: self.x - 1,
-                y: self.y,
+                ..*self
             })
         } else {
             None
         }
     }
 
-    fn right(&self, max: usize) -> Option<Point> {
-        if self.x + 1 < max {
+    fn right(&self, dims: &Dimensions) -> Option<Point> {
+        if self.x + 1 < dims.width {
             Some(Point {
                 x: self.x + 1,
-                y: self.y,
+                ..*self
             })
         } else {
             None
@@ -51,123 +121,328 @@ impl Point {
     fn up(&self) -> Option<Point> {
         if self.y > 0 {
             Some(Point {
-                x: self.x,
                 y: self.y - 1,
+                ..*self
             })
         } else {
             None
         }
     }
 
-    fn down(&self, max: usize) -> Option<Point> {
-        if self.y + 1 < max {
+    fn down(&self, dims: &Dimensions) -> Option<Point> {
+        if self.y + 1 < dims.height {
             Some(Point {
-                x: self.x,
                 y: self.y + 1,
+                ..*self
+            })
+        } else {
+            None
+        }
+    }
+
+    fn prev_layer(&self) -> Option<Point> {
+        if self.z > 0 {
+            Some(Point {
+                z: self.z - 1,
+                ..*self
             })
         } else {
             None
         }
     }
 
-    fn get_basin_size(&self, matrix: &[Vec<usize>], visited: &mut HashSet<Point>) -> usize {
-        let dirs: Vec<Option<Point>> = vec![
+    fn next_layer(&self, dims: &Dimensions) -> Option<Point> {
+        if self.z + 1 < dims.depth {
+            Some(Point {
+                z: self.z + 1,
+                ..*self
+            })
+        } else {
+            None
+        }
+    }
+
+    /// The 4 (in 2D) or 6 (in 3D) direct neighbours, which are inside of the map.
+    fn neighbors(&self, dims: &Dimensions) -> Vec<Point> {
+        vec![
             self.left(),
-            self.right(matrix[0].len()),
+            self.right(dims),
             self.up(),
-            self.down(matrix.len()),
-        ];
-        // let dir_vals: Vec<Option<usize>> = dirs
-        //     .iter()
-        //     .map(|elem| elem.map(|val| val.get_val(matrix)))
-        //     .collect();
-        let mut result = 1;
-        visited.insert(*self);
-        dirs.iter().for_each(|elem| {
-            if elem.is_some() {
-                if elem.map(|val| val.get_val(matrix)).unwrap() < 9
-                    && !visited.contains(&elem.unwrap())
-                {
-                    result += elem.unwrap().get_basin_size(matrix, visited);
-                }
-            }
-        });
-        result
+            self.down(dims),
+            self.prev_layer(),
+            self.next_layer(dims),
+        ]
+        .into_iter()
+        .flatten()
+        .collect()
     }
 
-    fn get_val(&self, matrix: &[Vec<usize>]) -> usize {
-        matrix[self.y][self.x]
+    fn reading_key(&self) -> (usize, usize, usize) {
+        (self.z, self.y, self.x)
     }
-}
 
-fn score_part2(matrix: &[Vec<usize>]) -> usize {
-    let mut basins: Vec<usize> = naive_minima(matrix)
-        .iter()
-        .map(|val| {
-            let mut visited: HashSet<Point> = HashSet::new();
-            val.get_basin_size(matrix, &mut visited)
-        })
-        .collect();
+    fn get_val(&self, map: &Heightmap) -> usize {
+        map.values[map.dims.index(self)]
+    }
 
-    basins.sort_unstable();
-    basins.reverse();
-    basins[0] * basins[1] * basins[2]
+    fn display(&self, dims: &Dimensions) -> String {
+        if dims.depth > 1 {
+            format!("{},{},{}", self.x, self.y, self.z)
+        } else {
+            format!("{},{}", self.x, self.y)
+        }
+    }
 }
 
-fn score_part1(matrix: &[Vec<usize>]) -> usize {
-    naive_minima(matrix)
-        .iter()
-        .fold(0, |acc, elem| acc + matrix[elem.y][elem.x] + 1)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Basin {
+    size: usize,
+    low_point: Point,
 }
 
-fn naive_minima(matrix: &[Vec<usize>]) -> Vec<Point> {
-    let mut result: Vec<Point> = Vec::new();
-    for (i, line) in matrix.iter().enumerate() {
-        for (j, elem) in line.iter().enumerate() {
-            let point = Point { x: j, y: i };
-            if check_pos_min(&point, matrix) {
-                result.push(point);
-            }
+/// Labelling of a heightmap, where each cell belongs to at most one basin.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct BasinMap {
+    dims: Dimensions,
+    /// basin id of each cell (indexed by [Dimensions::index]), [None] for cells which do not belong to any basin
+    labels: Vec<Option<usize>>,
+    basins: Vec<Basin>,
+    /// pairs of basins, which touch or are only separated by a single cell without basin
+    adjacency: BTreeSet<(usize, usize)>,
+}
+
+impl BasinMap {
+    fn new(map: &Heightmap, labels: Vec<Option<usize>>, basins: Vec<Basin>) -> Self {
+        let adjacency = Self::adjacency(&map.dims, &labels);
+        Self {
+            dims: map.dims,
+            labels,
+            basins,
+            adjacency,
         }
     }
-    result
-}
 
-fn check_pos_min(pos: &Point, matrix: &[Vec<usize>]) -> bool {
-    let elem = matrix[pos.y][pos.x];
-    let mut result: bool = true;
-    if pos.x > 0 {
-        if matrix[pos.y][pos.x - 1] <= elem {
-            result = false;
+    /// Labels the regions of cells, which are walled off by cells of height 9.
+    /// Each region is explored with a breadth first search, so the size of a basin is not limited by the stack.
+    fn walled(map: &Heightmap) -> Self {
+        let dims = &map.dims;
+        let mut labels = vec![None; dims.len()];
+        let mut basins = Vec::new();
+        for start in dims.points() {
+            if start.get_val(map) == 9 || labels[dims.index(&start)].is_some() {
+                continue;
+            }
+            let id = basins.len();
+            let mut basin = Basin {
+                size: 0,
+                low_point: start,
+            };
+            let mut queue = VecDeque::from([start]);
+            labels[dims.index(&start)] = Some(id);
+            while let Some(point) = queue.pop_front() {
+                basin.size += 1;
+                if point.get_val(map) < basin.low_point.get_val(map) {
+                    basin.low_point = point;
+                }
+                for next in point.neighbors(dims) {
+                    if next.get_val(map) < 9 && labels[dims.index(&next)].is_none() {
+                        labels[dims.index(&next)] = Some(id);
+                        queue.push_back(next);
+                    }
+                }
+            }
+            basins.push(basin);
         }
+        Self::new(map, labels, basins)
     }
-    if pos.x + 1 < matrix[pos.y].len() {
-        if matrix[pos.y][pos.x + 1] <= elem {
-            result = false;
+
+    /// Labels the cells by the sink they drain to, without treating any height as a wall.
+    ///
+    /// Each cell drains to its lowest neighbour, if that one is lower than the cell itself.
+    /// Ties are broken by taking the neighbour which comes first in reading order.
+    /// A plateau (connected cells of equal height) drains over its nearest outlet, i.e. the
+    /// nearest cell which has a lower neighbour. A plateau without outlet is a sink, whose
+    /// low point is its first cell in reading order.
+    fn drainage(map: &Heightmap) -> Self {
+        let dims = &map.dims;
+        let mut target: Vec<Option<Point>> = vec![None; dims.len()];
+        let mut explored = vec![false; dims.len()];
+        let mut labels = vec![None; dims.len()];
+        let mut basins = Vec::new();
+        for start in dims.points() {
+            if explored[dims.index(&start)] {
+                continue;
+            }
+            let plateau = Self::plateau(map, start, &mut explored);
+            let mut queue: VecDeque<Point> = VecDeque::new();
+            for point in plateau.iter() {
+                let lowest = point
+                    .neighbors(dims)
+                    .into_iter()
+                    .min_by_key(|elem| (elem.get_val(map), elem.reading_key()));
+                if let Some(lowest) = lowest.filter(|elem| elem.get_val(map) < point.get_val(map)) {
+                    target[dims.index(point)] = Some(lowest);
+                    queue.push_back(*point);
+                }
+            }
+            if queue.is_empty() {
+                let id = basins.len();
+                plateau
+                    .iter()
+                    .for_each(|point| labels[dims.index(point)] = Some(id));
+                basins.push(Basin {
+                    size: 0,
+                    low_point: plateau[0],
+                });
+            }
+            // the remaining cells of the plateau drain towards the nearest outlet
+            while let Some(point) = queue.pop_front() {
+                for next in Self::reading_order(point.neighbors(dims)) {
+                    if next.get_val(map) == point.get_val(map)
+                        && target[dims.index(&next)].is_none()
+                    {
+                        target[dims.index(&next)] = Some(point);
+                        queue.push_back(next);
+                    }
+                }
+            }
+        }
+        for start in dims.points() {
+            let mut path = vec![start];
+            let mut current = start;
+            while labels[dims.index(&current)].is_none() {
+                current = target[dims.index(&current)].unwrap();
+                path.push(current);
+            }
+            let label = labels[dims.index(&current)];
+            path.iter()
+                .for_each(|point| labels[dims.index(point)] = label);
         }
+        labels
+            .iter()
+            .for_each(|label| basins[label.unwrap()].size += 1);
+        Self::new(map, labels, basins)
     }
-    if pos.y > 0 {
-        if matrix[pos.y - 1][pos.x] <= elem {
-            result = false;
+
+    fn reading_order(mut points: Vec<Point>) -> Vec<Point> {
+        points.sort_unstable_by_key(Point::reading_key);
+        points
+    }
+
+    /// All cells of the same height, which are connected to the start, in reading order.
+    fn plateau(map: &Heightmap, start: Point, explored: &mut [bool]) -> Vec<Point> {
+        let dims = &map.dims;
+        let mut result = Vec::new();
+        let mut queue = VecDeque::from([start]);
+        explored[dims.index(&start)] = true;
+        while let Some(point) = queue.pop_front() {
+            result.push(point);
+            for next in point.neighbors(dims) {
+                if next.get_val(map) == start.get_val(map) && !explored[dims.index(&next)] {
+                    explored[dims.index(&next)] = true;
+                    queue.push_back(next);
+                }
+            }
         }
+        Self::reading_order(result)
+    }
+
+    /// Sum of the risk levels of the low points of all basins.
+    fn risk(&self, map: &Heightmap) -> usize {
+        self.basins
+            .iter()
+            .fold(0, |acc, elem| acc + elem.low_point.get_val(map) + 1)
     }
-    if pos.y + 1 < matrix.len() {
-        if matrix[pos.y + 1][pos.x] <= elem {
-            result = false;
+
+    fn adjacency(dims: &Dimensions, labels: &[Option<usize>]) -> BTreeSet<(usize, usize)> {
+        let mut result = BTreeSet::new();
+        for point in dims.points() {
+            let around: BTreeSet<usize> = point
+                .neighbors(dims)
+                .iter()
+                .filter_map(|elem| labels[dims.index(elem)])
+                .chain(labels[dims.index(&point)])
+                .collect();
+            for left in around.iter() {
+                for right in around.range(left + 1..) {
+                    result.insert((*left, *right));
+                }
+            }
         }
+        result
+    }
+
+    /// Sizes of the `count` largest basins, in descending order.
+    fn largest(&self, count: usize) -> Vec<usize> {
+        let mut sizes: Vec<usize> = self.basins.iter().map(|elem| elem.size).collect();
+        sizes.sort_unstable_by(|left, right| right.cmp(left));
+        sizes.truncate(count);
+        sizes
+    }
+
+    /// Textual export of the label map, the basins and their adjacency.
+    /// The layers of a 3D map are separated by blank lines.
+    fn export(&self) -> String {
+        let mut result = String::from("# label map, cells without basin are marked with '.'\n");
+        self.labels
+            .chunks(self.dims.width * self.dims.height)
+            .enumerate()
+            .for_each(|(z, layer)| {
+                if z > 0 {
+                    result += "\n";
+                }
+                layer.chunks(self.dims.width).for_each(|line| {
+                    let line: Vec<String> = line
+                        .iter()
+                        .map(|elem| match elem {
+                            Some(id) => id.to_string(),
+                            None => ".".to_string(),
+                        })
+                        .collect();
+                    result += &format!("{}\n", line.join(" "));
+                });
+            });
+        result += "# basin size low_point\n";
+        self.basins.iter().enumerate().for_each(|(id, basin)| {
+            result += &format!(
+                "{} {} {}\n",
+                id,
+                basin.size,
+                basin.low_point.display(&self.dims)
+            );
+        });
+        result += "# adjacent basins\n";
+        self.adjacency.iter().for_each(|(left, right)| {
+            result += &format!("{}-{}\n", left, right);
+        });
+        result
     }
-    result
 }
 
-fn decimal(input: &str) -> IResult<&str, &str> {
-    recognize(many1(terminated(
-        one_of("0123456789"),
-        many0(nom::character::complete::char('_')),
-    )))(input)
+fn score_part2(basins: &BasinMap) -> usize {
+    basins.largest(3).iter().product()
 }
 
-fn decimal_value(input: &str) -> IResult<&str, usize> {
-    map_res(decimal, |val: &str| val.parse::<usize>())(input)
+fn score_part1(map: &Heightmap) -> usize {
+    naive_minima(map)
+        .iter()
+        .fold(0, |acc, elem| acc + elem.get_val(map) + 1)
+}
+
+fn naive_minima(map: &Heightmap) -> Vec<Point> {
+    map.dims
+        .points()
+        .filter(|point| check_pos_min(point, map))
+        .collect()
+}
+
+/// Checks for a strict local minimum, so no cell of a plateau is ever a minimum.
+/// [BasinMap::drainage] treats plateaus without outlet as sinks instead.
+fn check_pos_min(pos: &Point, map: &Heightmap) -> bool {
+    let elem = pos.get_val(map);
+    pos.neighbors(&map.dims)
+        .iter()
+        .all(|other| other.get_val(map) > elem)
 }
 
 fn digit_value(input: &str) -> IResult<&str, usize> {
@@ -182,7 +457,144 @@ fn parse_line(input: &str) -> IResult<&str, Vec<usize>> {
     terminated(many1(digit_value), line_ending)(input)
 }
 
-fn parse(input: &str) -> Vec<Vec<usize>> {
-    let (inp, result) = many1(parse_line)(input).unwrap();
-    result
+fn parse_layer(input: &str) -> IResult<&str, Vec<Vec<usize>>> {
+    many1(parse_line)(input)
+}
+
+/// Parses a single 2D layer or a stack of layers, which are separated by blank lines.
+fn parse(input: &str) -> Heightmap {
+    Heightmap::new(separated_list1(line_ending, parse_layer)(input).unwrap().1)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const EXAMPLE: &str = "2199943210
+3987894921
+9856789892
+8767896789
+9899965678
+";
+
+    #[test]
+    fn basins() {
+        let map = parse(EXAMPLE);
+        let basins = BasinMap::walled(&map);
+        assert_eq!(score_part1(&map), 15);
+        assert_eq!(basins.largest(4), vec![14, 9, 9, 3]);
+        assert_eq!(score_part2(&basins), 1134);
+        assert_eq!(
+            basins.basins[0],
+            Basin {
+                size: 3,
+                low_point: Point { x: 1, y: 0, z: 0 }
+            }
+        );
+        assert_eq!(
+            basins.labels[map.dims.index(&Point { x: 0, y: 2, z: 0 })],
+            None
+        );
+        assert_eq!(
+            basins.labels[map.dims.index(&Point { x: 9, y: 4, z: 0 })],
+            Some(3)
+        );
+        assert_eq!(
+            basins.adjacency,
+            BTreeSet::from([(0, 2), (1, 2), (1, 3), (2, 3)])
+        );
+    }
+
+    #[test]
+    fn export() {
+        let basins = BasinMap::walled(&parse("129\n991\n"));
+        assert_eq!(
+            basins.export(),
+            "# label map, cells without basin are marked with '.'
+0 0 .
+. . 1
+# basin size low_point
+0 2 0,0
+1 1 2,1
+# adjacent basins
+0-1
+"
+        );
+    }
+
+    #[test]
+    fn drainage() {
+        let map = parse("5551\n5552\n0553\n");
+        let basins = BasinMap::drainage(&map);
+        assert_eq!(
+            basins.labels,
+            vec![
+                Some(1),
+                Some(0),
+                Some(0),
+                Some(0),
+                Some(1),
+                Some(1),
+                Some(0),
+                Some(0),
+                Some(1),
+                Some(1),
+                Some(0),
+                Some(0),
+            ]
+        );
+        assert_eq!(basins.largest(3), vec![7, 5]);
+        assert_eq!(basins.risk(&map), 3);
+        assert_eq!(basins.adjacency, BTreeSet::from([(0, 1)]));
+    }
+
+    #[test]
+    fn drainage_plateau_sink() {
+        let map = parse("112\n222\n");
+        // the plateau of height 1 does not contain a strict minimum
+        assert!(naive_minima(&map).is_empty());
+        let basins = BasinMap::drainage(&map);
+        assert_eq!(
+            basins.basins,
+            vec![Basin {
+                size: 6,
+                low_point: Point { x: 0, y: 0, z: 0 }
+            }]
+        );
+        assert_eq!(basins.risk(&map), 2);
+    }
+
+    #[test]
+    fn voxels() {
+        let map = parse("999\n919\n999\n\n929\n939\n299\n\n919\n999\n999\n");
+        assert_eq!(
+            map.dims,
+            Dimensions {
+                width: 3,
+                height: 3,
+                depth: 3
+            }
+        );
+        assert_eq!(Point { x: 1, y: 1, z: 1 }.neighbors(&map.dims).len(), 6);
+        assert_eq!(Point { x: 0, y: 0, z: 0 }.neighbors(&map.dims).len(), 3);
+        assert_eq!(
+            naive_minima(&map),
+            vec![
+                Point { x: 1, y: 1, z: 0 },
+                Point { x: 0, y: 2, z: 1 },
+                Point { x: 1, y: 0, z: 2 }
+            ]
+        );
+        assert_eq!(score_part1(&map), 7);
+        let basins = BasinMap::walled(&map);
+        assert_eq!(basins.largest(3), vec![4, 1]);
+        assert_eq!(basins.basins[0].low_point, Point { x: 1, y: 1, z: 0 });
+        assert_eq!(
+            basins.labels[map.dims.index(&Point { x: 1, y: 0, z: 2 })],
+            Some(0)
+        );
+        assert!(basins
+            .export()
+            .contains(". . .\n. 0 .\n. . .\n\n. 0 .\n. 0 .\n1 . .\n\n. 0 .\n"));
+    }
 }
@@ -1,11 +1,52 @@
 use std::io::BufRead;
 
 fn main() {
-    let file = std::fs::File::open(std::env::args().last().unwrap()).unwrap();
+    let args: Vec<String> = std::env::args().collect();
+    let options = Options {
+        recover: args.iter().any(|arg| arg == "--recover"),
+        report_unknown: args.iter().any(|arg| arg == "--report-unknown"),
+    };
+    let config = match args.iter().position(|arg| arg == "--config") {
+        Some(pos) => Config::parse(&std::fs::read_to_string(&args[pos + 1]).unwrap())
+            .unwrap_or_else(|err| {
+                eprintln!("invalid config: {}", err);
+                std::process::exit(1);
+            }),
+        None => Config::default(),
+    };
+
+    if let Some(pos) = args.iter().position(|arg| arg == "--fix") {
+        let input = std::fs::read_to_string(args.last().unwrap()).unwrap();
+        let mut patched = String::new();
+        input.lines().enumerate().for_each(|(nr, line)| {
+            let (fixed, changes) = fix_line(line, &config);
+            changes
+                .iter()
+                .for_each(|change| println!("{}:{}", nr + 1, change));
+            patched += &fixed;
+            patched.push('\n');
+        });
+        std::fs::write(&args[pos + 1], patched).unwrap();
+        return;
+    }
+
+    if args.iter().any(|arg| arg == "--lint") {
+        let file = std::fs::File::open(args.last().unwrap()).unwrap();
+        let buffer = std::io::BufReader::new(file);
+        buffer.lines().enumerate().for_each(|(nr, elem)| {
+            check_line(elem.unwrap().as_str(), &config, &options)
+                .diagnostics
+                .iter()
+                .for_each(|diagnostic| println!("{}:{}", nr + 1, diagnostic));
+        });
+        return;
+    }
+
+    let file = std::fs::File::open(args.last().unwrap()).unwrap();
     let buffer = std::io::BufReader::new(file);
 
     let result = buffer.lines().fold(0, |acc: usize, elem| {
-        let result = parse_line(elem.unwrap().as_str())
+        let result = parse_line(elem.unwrap().as_str(), &config)
             .value_invalid()
             .unwrap_or(0);
         acc + result
@@ -13,12 +54,12 @@ fn main() {
 
     println!("{}", result);
 
-    let file = std::fs::File::open(std::env::args().last().unwrap()).unwrap();
+    let file = std::fs::File::open(args.last().unwrap()).unwrap();
     let buffer = std::io::BufReader::new(file);
 
     let mut resultlist: Vec<usize> = buffer
         .lines()
-        .filter_map(|elem| parse_line(elem.unwrap().as_str()).value_incomplete())
+        .filter_map(|elem| parse_line(elem.unwrap().as_str(), &config).value_incomplete())
         .collect();
     resultlist.sort_unstable();
 
@@ -35,13 +76,6 @@ enum Result {
 }
 
 impl Result {
-    fn value(&self) -> Option<usize> {
-        match self {
-            Result::Invalid(val) | Result::Incomplete(val) => Some(*val),
-            Result::Ok => None,
-        }
-    }
-
     fn value_invalid(&self) -> Option<usize> {
         match self {
             Result::Invalid(val) => Some(*val),
@@ -59,62 +93,664 @@ impl Result {
     }
 }
 
-fn parse_line(input: &str) -> Result {
-    let mut list: Vec<char> = Vec::with_capacity(input.len());
-    for i in input.chars() {
-        match i {
-            '(' | '[' | '<' | '{' => {
-                list.push(i);
+/// The delimiters and scores of the AoC navigation subsystem.
+const DEFAULT_CONFIG: &str = "# kind open close error-score completion-score
+pair ( ) 3 1
+pair [ ] 57 2
+pair { } 1197 3
+pair < > 25137 4
+";
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct Delimiter {
+    open: String,
+    close: String,
+    /// everything between the tokens of a quote is ignored, so quotes do not nest
+    quote: bool,
+    /// score of a line, which is corrupted by an unexpected `close` token
+    error_score: usize,
+    /// score for each `close` token, which completes an incomplete line
+    completion_score: usize,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Token {
+    Open(usize),
+    Close(usize),
+}
+
+/// The delimiter pairs of a language together with their scores.
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct Config {
+    delimiters: Vec<Delimiter>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self::parse(DEFAULT_CONFIG).unwrap()
+    }
+}
+
+impl Config {
+    /// Parses one delimiter per line as `<pair|quote> <open> <close> <error-score> <completion-score>`.
+    /// Empty lines and lines starting with `#` are ignored. No two delimiters share an opening or
+    /// a closing token, and only a quote may open and close with the same token.
+    fn parse(input: &str) -> std::result::Result<Self, String> {
+        let mut delimiters: Vec<Delimiter> = Vec::new();
+        for (nr, line) in input.lines().enumerate() {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            if fields.is_empty() || fields[0].starts_with('#') {
+                continue;
             }
-            ')' | ']' | '>' | '}' => match list.pop() {
-                Some(last) => {
-                    if !compparens(last, i) {
-                        return Result::Invalid(penalty(i));
+            let score = |field: &str| {
+                field
+                    .parse::<usize>()
+                    .map_err(|_| format!("line {}: invalid score '{}'", nr + 1, field))
+            };
+            let delimiter = match fields[..] {
+                [kind @ ("pair" | "quote"), open, close, error_score, completion_score] => {
+                    Delimiter {
+                        open: open.to_string(),
+                        close: close.to_string(),
+                        quote: kind == "quote",
+                        error_score: score(error_score)?,
+                        completion_score: score(completion_score)?,
                     }
                 }
-                None => {
-                    return Result::Invalid(penalty(i));
-                }
-            },
-            _ => unreachable!(),
+                _ => return Err(format!("line {}: malformed delimiter", nr + 1)),
+            };
+            if !delimiter.quote && delimiter.open == delimiter.close {
+                return Err(format!(
+                    "line {}: pair '{}' opens and closes with the same token, use a quote",
+                    nr + 1,
+                    delimiter.open
+                ));
+            }
+            if let Some(other) = delimiters.iter().find(|other| other.open == delimiter.open) {
+                return Err(format!(
+                    "line {}: '{}' already opens '{}'",
+                    nr + 1,
+                    delimiter.open,
+                    other.close
+                ));
+            }
+            if let Some(other) = delimiters
+                .iter()
+                .find(|other| other.close == delimiter.close)
+            {
+                return Err(format!(
+                    "line {}: '{}' already closes '{}'",
+                    nr + 1,
+                    delimiter.close,
+                    other.open
+                ));
+            }
+            delimiters.push(delimiter);
         }
+        Ok(Self { delimiters })
     }
-    if list.is_empty() {
-        Result::Ok
-    } else {
-        list.reverse();
-        Result::Incomplete(autocomplete_penalty(&list))
+
+    /// Checks whether the input starts with the token. Tokens made of alphanumeric characters only
+    /// match whole words, so `end` is not found in `ending`.
+    fn matches(input: &str, prev: Option<char>, token: &str) -> bool {
+        if !input.starts_with(token) {
+            return false;
+        }
+        if token.chars().all(char::is_alphanumeric) {
+            let next = input[token.len()..].chars().next();
+            !prev.is_some_and(char::is_alphanumeric) && !next.is_some_and(char::is_alphanumeric)
+        } else {
+            true
+        }
+    }
+
+    /// The longest token at the start of the input, an opening one wins a tie.
+    fn token(&self, input: &str, prev: Option<char>) -> Option<(Token, &str)> {
+        self.delimiters
+            .iter()
+            .enumerate()
+            .flat_map(|(idx, elem)| {
+                [
+                    (Token::Open(idx), elem.open.as_str()),
+                    (Token::Close(idx), elem.close.as_str()),
+                ]
+            })
+            .filter(|(_, token)| Self::matches(input, prev, token))
+            .fold(None, |acc: Option<(Token, &str)>, elem| match acc {
+                Some(best) if best.1.len() >= elem.1.len() => Some(best),
+                _ => Some(elem),
+            })
+    }
+
+    fn close(&self, idx: usize) -> &str {
+        &self.delimiters[idx].close
+    }
+
+    fn penalty(&self, idx: usize) -> usize {
+        self.delimiters[idx].error_score
     }
+
+    fn complete_penalty(&self, idx: usize) -> usize {
+        self.delimiters[idx].completion_score
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+struct Options {
+    /// keep scanning after an error, instead of stopping at the first one
+    recover: bool,
+    /// report characters which are not part of a token, instead of ignoring them
+    report_unknown: bool,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum DiagnosticKind {
+    /// a closing token which does not match the last open one
+    Mismatch,
+    /// a closing token without any open one
+    Unopened,
+    /// the line ends while delimiters are still open
+    Unclosed,
+    /// a character which is not part of a token
+    Unknown,
 }
 
-fn compparens(l: char, r: char) -> bool {
-    (l == '(' && r == ')')
-        || (l == '[' && r == ']')
-        || (l == '{' && r == '}')
-        || (l == '<' && r == '>')
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct Diagnostic {
+    kind: DiagnosticKind,
+    /// column of the start of the offending token, starting at 1
+    column: usize,
+    /// the closing token, which would have been valid
+    expected: Option<String>,
+    /// [None] at the end of the line
+    found: Option<String>,
+    /// the delimiter closed by the found token
+    delimiter: Option<usize>,
 }
 
-fn penalty(c: char) -> usize {
-    match c {
-        ')' => 3,
-        ']' => 57,
-        '}' => 1197,
-        '>' => 25137,
-        _ => 0,
+impl std::fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let found = match &self.found {
+            Some(token) => format!("'{}'", token),
+            None => "end of line".to_string(),
+        };
+        match (self.kind, &self.expected) {
+            (DiagnosticKind::Unknown, _) => {
+                write!(f, "{}: unexpected character {}", self.column, found)
+            }
+            (_, Some(expected)) => {
+                write!(
+                    f,
+                    "{}: expected '{}', found {}",
+                    self.column, expected, found
+                )
+            }
+            (_, None) => write!(f, "{}: nothing open, found {}", self.column, found),
+        }
     }
 }
 
-fn complete_penalty(c: char) -> usize {
-    match c {
-        '(' => 1,
-        '[' => 2,
-        '{' => 3,
-        '<' => 4,
-        _ => 0,
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+struct Report {
+    diagnostics: Vec<Diagnostic>,
+    /// delimiters which are still open at the end of the line, innermost last
+    open: Vec<usize>,
+}
+
+/// Checks the delimiters of a line and reports the position of each error.
+///
+/// With [Options::recover], an offending closing token is skipped and scanning continues
+/// with the same open delimiters, so each error of the line gets reported.
+/// Whitespace is never reported.
+fn check_line(input: &str, config: &Config, options: &Options) -> Report {
+    let mut report = Report::default();
+    let chars: Vec<(usize, char)> = input.char_indices().collect();
+    let mut i = 0;
+    while i < chars.len() {
+        let (pos, c) = chars[i];
+        let rest = &input[pos..];
+        let prev = i.checked_sub(1).map(|prev| chars[prev].1);
+        let column = i + 1;
+        if let Some(&quote) = report
+            .open
+            .last()
+            .filter(|idx| config.delimiters[**idx].quote)
+        {
+            if Config::matches(rest, prev, config.close(quote)) {
+                report.open.pop();
+                i += config.close(quote).chars().count();
+            } else {
+                i += 1;
+            }
+            continue;
+        }
+        match config.token(rest, prev) {
+            Some((Token::Open(idx), token)) => {
+                report.open.push(idx);
+                i += token.chars().count();
+            }
+            Some((Token::Close(idx), token)) => {
+                i += token.chars().count();
+                match report.open.last() {
+                    Some(last) if *last == idx => {
+                        report.open.pop();
+                    }
+                    last => {
+                        report.diagnostics.push(Diagnostic {
+                            kind: match last {
+                                Some(_) => DiagnosticKind::Mismatch,
+                                None => DiagnosticKind::Unopened,
+                            },
+                            column,
+                            expected: last.map(|last| config.close(*last).to_string()),
+                            found: Some(token.to_string()),
+                            delimiter: Some(idx),
+                        });
+                        if !options.recover {
+                            return report;
+                        }
+                    }
+                }
+            }
+            None => {
+                i += 1;
+                if options.report_unknown && !c.is_whitespace() {
+                    report.diagnostics.push(Diagnostic {
+                        kind: DiagnosticKind::Unknown,
+                        column,
+                        expected: None,
+                        found: Some(c.to_string()),
+                        delimiter: None,
+                    });
+                    if !options.recover {
+                        return report;
+                    }
+                }
+            }
+        }
+    }
+    if let Some(last) = report.open.last() {
+        report.diagnostics.push(Diagnostic {
+            kind: DiagnosticKind::Unclosed,
+            column: chars.len() + 1,
+            expected: Some(config.close(*last).to_string()),
+            found: None,
+            delimiter: None,
+        });
     }
+    report
 }
 
-fn autocomplete_penalty(list: &[char]) -> usize {
+fn parse_line(input: &str, config: &Config) -> Result {
+    let mut report = check_line(input, config, &Options::default());
+    match report.diagnostics.first() {
+        Some(Diagnostic {
+            kind: DiagnosticKind::Mismatch | DiagnosticKind::Unopened,
+            delimiter: Some(idx),
+            ..
+        }) => Result::Invalid(config.penalty(*idx)),
+        _ if report.open.is_empty() => Result::Ok,
+        _ => {
+            report.open.reverse();
+            Result::Incomplete(autocomplete_penalty(&report.open, config))
+        }
+    }
+}
+
+fn autocomplete_penalty(list: &[usize], config: &Config) -> usize {
     list.iter()
-        .fold(0, |acc, elem| (acc * 5) + complete_penalty(*elem))
+        .fold(0, |acc, elem| (acc * 5) + config.complete_penalty(*elem))
+}
+
+/// A single change of [fix_line], the columns refer to the original line.
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Change {
+    Replaced {
+        column: usize,
+        found: String,
+        with: String,
+    },
+    Deleted {
+        column: usize,
+        found: String,
+    },
+    Appended {
+        column: usize,
+        completion: String,
+    },
+    /// no single replacement or deletion removes the error at this column
+    Unfixable {
+        column: usize,
+    },
+}
+
+impl std::fmt::Display for Change {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Change::Replaced {
+                column,
+                found,
+                with,
+            } => write!(f, "{}: replaced '{}' with '{}'", column, found, with),
+            Change::Deleted { column, found } => write!(f, "{}: deleted '{}'", column, found),
+            Change::Appended { column, completion } => {
+                write!(f, "{}: appended '{}'", column, completion)
+            }
+            Change::Unfixable { column } => {
+                write!(
+                    f,
+                    "{}: no single replacement or deletion repairs the line",
+                    column
+                )
+            }
+        }
+    }
+}
+
+fn is_corrupted(input: &str, config: &Config) -> bool {
+    matches!(
+        check_line(input, config, &Options::default())
+            .diagnostics
+            .first(),
+        Some(Diagnostic {
+            kind: DiagnosticKind::Mismatch | DiagnosticKind::Unopened,
+            ..
+        })
+    )
+}
+
+/// Replaces `len` characters starting at the character index `start`.
+fn splice(input: &str, start: usize, len: usize, with: &str) -> String {
+    let chars: Vec<char> = input.chars().collect();
+    chars[..start]
+        .iter()
+        .chain(with.chars().collect::<Vec<_>>().iter())
+        .chain(chars[start + len..].iter())
+        .collect()
+}
+
+/// The closing tokens for all open delimiters, words are separated by a space.
+fn completion(open: &[usize], config: &Config) -> String {
+    open.iter().rev().fold(String::new(), |acc, elem| {
+        let close = config.close(*elem);
+        if close.chars().all(char::is_alphanumeric) {
+            format!("{} {}", acc, close)
+        } else {
+            acc + close
+        }
+    })
+}
+
+/// Repairs a corrupted line with a single replacement or deletion of a token.
+///
+/// The offending token itself is tried first, replaced by the expected closing token or deleted.
+/// Otherwise each token of the line is tried from left to right, replaced by any other token or
+/// deleted. The first edit without corruption wins. An incomplete line gets its completion appended.
+fn fix_line(input: &str, config: &Config) -> (String, Vec<Change>) {
+    let report = check_line(input, config, &Options::default());
+    let mut changes = Vec::new();
+    let mut fixed = input.to_string();
+    if let Some(Diagnostic {
+        kind: DiagnosticKind::Mismatch | DiagnosticKind::Unopened,
+        column,
+        expected,
+        found: Some(found),
+        ..
+    }) = report.diagnostics.first()
+    {
+        let chars: Vec<char> = input.chars().collect();
+        let tokens: Vec<(usize, &str)> = (0..chars.len())
+            .filter_map(|pos| {
+                let rest: String = chars[pos..].iter().collect();
+                let prev = pos.checked_sub(1).map(|prev| chars[prev]);
+                config.token(&rest, prev).map(|(_, token)| (pos, token))
+            })
+            .collect();
+        let replacements: Vec<&str> = config
+            .delimiters
+            .iter()
+            .flat_map(|elem| [elem.open.as_str(), elem.close.as_str()])
+            .collect();
+        let first = expected
+            .iter()
+            .map(|expected| (column - 1, found.as_str(), Some(expected.as_str())))
+            .chain([(column - 1, found.as_str(), None)]);
+        let others = tokens.iter().flat_map(|(pos, token)| {
+            replacements
+                .iter()
+                .filter(move |with| *with != token)
+                .map(move |with| (*pos, *token, Some(*with)))
+                .chain([(*pos, *token, None)])
+        });
+        let repair = first.chain(others).find_map(|(pos, token, with)| {
+            let candidate = splice(input, pos, token.chars().count(), with.unwrap_or(""));
+            (!is_corrupted(&candidate, config)).then_some((candidate, pos, token, with))
+        });
+        match repair {
+            Some((candidate, pos, token, with)) => {
+                changes.push(match with {
+                    Some(with) => Change::Replaced {
+                        column: pos + 1,
+                        found: token.to_string(),
+                        with: with.to_string(),
+                    },
+                    None => Change::Deleted {
+                        column: pos + 1,
+                        found: token.to_string(),
+                    },
+                });
+                fixed = candidate;
+            }
+            None => {
+                changes.push(Change::Unfixable { column: *column });
+                return (fixed, changes);
+            }
+        }
+    }
+    let open = check_line(&fixed, config, &Options::default()).open;
+    if !open.is_empty() {
+        let completion = completion(&open, config);
+        changes.push(Change::Appended {
+            column: input.chars().count() + 1,
+            completion: completion.clone(),
+        });
+        fixed += &completion;
+    }
+    (fixed, changes)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const PASCAL: &str = "
+# kind open close error-score completion-score
+pair begin end 10 1
+pair ( ) 20 2
+quote (* *) 30 3
+quote ' ' 40 4
+";
+
+    #[test]
+    fn scores() {
+        let config = Config::default();
+        assert_eq!(
+            parse_line("{([(<{}[<>[]}>{[]{[(<()>", &config).value_invalid(),
+            Some(1197)
+        );
+        assert_eq!(
+            parse_line("[({(<(())[]>[[{[]{<()<>>", &config).value_incomplete(),
+            Some(288957)
+        );
+        assert!(matches!(
+            parse_line("[<>({}){}[([])<>]]", &config),
+            Result::Ok
+        ));
+    }
+
+    #[test]
+    fn diagnostics() {
+        let config = Config::default();
+        let report = check_line("{([(<{}[<>[]}>{[]{[(<()>", &config, &Options::default());
+        assert_eq!(
+            report.diagnostics,
+            vec![Diagnostic {
+                kind: DiagnosticKind::Mismatch,
+                column: 13,
+                expected: Some("]".to_string()),
+                found: Some("}".to_string()),
+                delimiter: Some(2),
+            }]
+        );
+        assert_eq!(
+            report.diagnostics[0].to_string(),
+            "13: expected ']', found '}'"
+        );
+
+        let report = check_line("(]", &config, &Options::default());
+        assert_eq!(report.diagnostics.len(), 1);
+        let report = check_line(
+            "(]",
+            &config,
+            &Options {
+                recover: true,
+                ..Default::default()
+            },
+        );
+        assert_eq!(
+            report.diagnostics[1],
+            Diagnostic {
+                kind: DiagnosticKind::Unclosed,
+                column: 3,
+                expected: Some(")".to_string()),
+                found: None,
+                delimiter: None,
+            }
+        );
+        assert_eq!(
+            report.diagnostics[1].to_string(),
+            "3: expected ')', found end of line"
+        );
+    }
+
+    #[test]
+    fn recovery_and_unknown() {
+        let config = Config::default();
+        let options = Options {
+            recover: true,
+            report_unknown: true,
+        };
+        let report = check_line(")(a>)]", &config, &options);
+        assert_eq!(
+            report
+                .diagnostics
+                .iter()
+                .map(|elem| (elem.kind, elem.column))
+                .collect::<Vec<_>>(),
+            vec![
+                (DiagnosticKind::Unopened, 1),
+                (DiagnosticKind::Unknown, 3),
+                (DiagnosticKind::Mismatch, 4),
+                (DiagnosticKind::Unopened, 6),
+            ]
+        );
+        assert!(report.open.is_empty());
+        // other characters are ignored by default
+        assert!(check_line("(a)", &config, &Options::default())
+            .diagnostics
+            .is_empty());
+    }
+
+    #[test]
+    fn multi_character_tokens() {
+        let config = Config::parse(PASCAL).unwrap();
+        assert!(matches!(
+            parse_line("begin x := (1); (* (end *) ending end", &config),
+            Result::Ok
+        ));
+        assert!(matches!(
+            parse_line("begin writeln(')') (* ", &config),
+            Result::Incomplete(16)
+        ));
+        assert!(matches!(
+            parse_line("begin (x end", &config),
+            Result::Invalid(10)
+        ));
+        let report = check_line("begin (x end", &config, &Options::default());
+        assert_eq!(
+            report.diagnostics[0].to_string(),
+            "10: expected ')', found 'end'"
+        );
+    }
+
+    #[test]
+    fn config_errors() {
+        assert_eq!(
+            Config::parse("pair ( ) 3 1\npair ( ] 3 1\n"),
+            Err("line 2: '(' already opens ')'".to_string())
+        );
+        assert_eq!(
+            Config::parse("pair ( ) 3\n"),
+            Err("line 1: malformed delimiter".to_string())
+        );
+        assert_eq!(
+            Config::parse("quote ' ' x 1\n"),
+            Err("line 1: invalid score 'x'".to_string())
+        );
+        assert_eq!(
+            Config::parse("# comment\npair | | 3 1\n"),
+            Err("line 2: pair '|' opens and closes with the same token, use a quote".to_string())
+        );
+        assert!(Config::parse("quote | | 3 1\n").is_ok());
+        assert_eq!(
+            Config::parse("pair ( ) 3 1\npair begin ) 3 1\n"),
+            Err("line 2: ')' already closes '('".to_string())
+        );
+    }
+
+    #[test]
+    fn fix() {
+        let config = Config::default();
+        assert_eq!(
+            fix_line("[({(<(())[]>[[{[]{<()<>>", &config),
+            (
+                "[({(<(())[]>[[{[]{<()<>>}}]])})]".to_string(),
+                vec![Change::Appended {
+                    column: 25,
+                    completion: "}}]])})]".to_string()
+                }]
+            )
+        );
+        let (fixed, changes) = fix_line("{([(<{}[<>[]}>{[]{[(<()>", &config);
+        assert_eq!(
+            changes[0],
+            Change::Replaced {
+                column: 13,
+                found: "}".to_string(),
+                with: "]".to_string()
+            }
+        );
+        assert!(matches!(parse_line(&fixed, &config), Result::Ok));
+        assert_eq!(
+            fix_line("(()))", &config),
+            (
+                "(())".to_string(),
+                vec![Change::Deleted {
+                    column: 5,
+                    found: ")".to_string()
+                }]
+            )
+        );
+        assert_eq!(
+            fix_line("(]]]", &config),
+            ("(]]]".to_string(), vec![Change::Unfixable { column: 2 }])
+        );
+        assert_eq!(fix_line("()", &config), ("()".to_string(), Vec::new()));
+    }
+
+    #[test]
+    fn fix_words() {
+        let config = Config::parse(PASCAL).unwrap();
+        assert_eq!(fix_line("begin (x", &config).0, "begin (x) end");
+    }
 }
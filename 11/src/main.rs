@@ -1,13 +1,9 @@
-use std::collections::HashSet;
+use std::collections::HashMap;
 
 use nom::{
-    bytes::complete::{tag, take_while_m_n},
-    character::{
-        complete::{char, line_ending, one_of},
-        is_digit,
-    },
+    character::complete::{line_ending, one_of},
     combinator::{map_res, recognize},
-    multi::{many0, many1, separated_list0, separated_list1},
+    multi::many1,
     sequence::terminated,
     IResult,
 };
@@ -15,76 +11,362 @@ use nom::{
 use itertools::Itertools;
 
 fn main() {
-    let input = std::fs::read_to_string(std::env::args().last().unwrap()).unwrap();
-    let mut field = octo_array(&input);
-    init_pos(&mut field);
-    println!(
-        "{}",
-        (1..101)
-            .into_iter()
-            .fold(0, |acc, s| { step(s, &mut field) + acc })
-    );
+    let args: Vec<String> = std::env::args().collect();
+    let toroidal = args.iter().any(|arg| arg == "--toroidal");
+    let input = std::fs::read_to_string(args.last().unwrap()).unwrap();
+    let mut field = Field::new(octo_array(&input), toroidal).unwrap_or_else(|err| {
+        eprintln!("{}", err);
+        std::process::exit(1);
+    });
+    if let Some(pos) = args.iter().position(|arg| arg == "--steps") {
+        let steps: usize = args[pos + 1].parse().unwrap();
+        let cycle = field.cycle();
+        println!(
+            "state after step {} repeats every {} steps",
+            cycle.start, cycle.period
+        );
+        println!("{}", cycle.flashes_after(steps));
+        return;
+    }
+
+    let animate = args.iter().any(|arg| arg == "--animate");
+    let frames = args
+        .iter()
+        .position(|arg| arg == "--frames")
+        .map(|pos| std::path::Path::new(&args[pos + 1]));
+    if animate || frames.is_some() {
+        let delay = std::time::Duration::from_millis(
+            args.iter()
+                .position(|arg| arg == "--delay")
+                .map(|pos| args[pos + 1].parse().unwrap())
+                .unwrap_or(100),
+        );
+        let limit: usize = args
+            .iter()
+            .position(|arg| arg == "--max-steps")
+            .map(|pos| args[pos + 1].parse().unwrap())
+            .unwrap_or(MAX_STEPS);
+        // frame numbers are padded to the same width, so the files sort by step
+        let width = limit.to_string().len();
+        if let Some(dir) = frames {
+            std::fs::create_dir_all(dir).unwrap();
+        }
+        // replays the steps up to the first synchronised flash or the step limit,
+        // whichever comes first, since some fields never synchronise
+        let mut s: usize = 0;
+        let mut flashes: usize = 0;
+        loop {
+            if animate {
+                print!("\x1b[2J\x1b[H{}", field.ansi());
+                println!("step {}: {} flashes", s, flashes);
+                std::thread::sleep(delay);
+            }
+            if let Some(dir) = frames {
+                std::fs::write(
+                    dir.join(format!("frame_{:0width$}.pgm", s, width = width)),
+                    field.pgm(),
+                )
+                .unwrap();
+            }
+            if flashes == field.len() || s == limit {
+                break;
+            }
+            s += 1;
+            flashes = field.step(s);
+        }
+        return;
+    }
+
+    let initial = field.clone();
+    let trace: Vec<Vec<Flash>> = (1..101).map(|s| field.trace_step(s)).collect();
+    println!("{}", trace.iter().map(Vec::len).sum::<usize>());
+    if let Some(pos) = args.iter().position(|arg| arg == "--trace-json") {
+        std::fs::write(&args[pos + 1], trace_json(&trace)).unwrap();
+    }
+    if let Some(pos) = args.iter().position(|arg| arg == "--trace-dot") {
+        std::fs::write(&args[pos + 1], trace_dot(&trace)).unwrap();
+    }
 
-    let mut field = octo_array(&input);
-    init_pos(&mut field);
+    let mut field = initial;
     let mut i: usize = 0;
     loop {
         i += 1;
-        if step(i, &mut field) == 100 {
+        if field.step(i) == field.len() {
             break;
         }
     }
     println!("{}", i);
 }
 
-fn init_pos(field: &mut Vec<Vec<Octopus>>) {
-    for (yidx, line) in field.iter_mut().enumerate() {
-        for (xidx, val) in line.iter_mut().enumerate() {
-            val.set_pos(Point::new(xidx, yidx));
-        }
-    }
+/// Default limit of steps replayed by the animation and the frame export.
+const MAX_STEPS: usize = 1000;
+
+/// A rectangular grid of octopuses.
+#[derive(Debug, Clone)]
+struct Field {
+    width: usize,
+    height: usize,
+    /// flashes reach over the borders to the opposite side
+    toroidal: bool,
+    octopuses: Vec<Vec<Octopus>>,
 }
 
-/// returns how many flares there have been in this step
-fn step(s: usize, field: &mut Vec<Vec<Octopus>>) -> usize {
-    let mut to_flare: HashSet<Point> = HashSet::new();
-    field.iter_mut().for_each(|line| {
-        line.iter_mut().for_each(|octo| {
-            if octo.increase() {
-                to_flare.insert(octo.pos.clone());
+impl Field {
+    fn new(mut octopuses: Vec<Vec<Octopus>>, toroidal: bool) -> Result<Self, String> {
+        let height = octopuses.len();
+        let width = octopuses.first().map(Vec::len).unwrap_or(0);
+        if let Some(yidx) = octopuses.iter().position(|line| line.len() != width) {
+            return Err(format!(
+                "line {}: expected {} octopuses, found {}",
+                yidx + 1,
+                width,
+                octopuses[yidx].len()
+            ));
+        }
+        for (yidx, line) in octopuses.iter_mut().enumerate() {
+            for (xidx, val) in line.iter_mut().enumerate() {
+                val.set_pos(Point::new(xidx, yidx));
             }
+        }
+        Ok(Self {
+            width,
+            height,
+            toroidal,
+            octopuses,
         })
-    });
-    let mut flared: bool = !to_flare.is_empty();
-    while flared {
-        let mut increase: Vec<Point> = Vec::new();
-        to_flare
-            .into_iter()
-            .for_each(|pt| increase.append(&mut field[pt.y][pt.x].flare_at(s)));
-        to_flare = HashSet::new();
-        increase.iter_mut().for_each(|octo| {
-            if field[octo.y][octo.x].increase() {
-                to_flare.insert(octo.clone());
+    }
+
+    /// number of octopuses in the field
+    fn len(&self) -> usize {
+        self.width * self.height
+    }
+
+    /// energy levels in reading order
+    fn levels(&self) -> Vec<usize> {
+        self.octopuses
+            .iter()
+            .flat_map(|line| line.iter().map(|octo| octo.level))
+            .collect()
+    }
+
+    /// Simulates a copy of the field until an energy state repeats, counting steps from the
+    /// current state. There are finitely many states, so this terminates, but it is only fast
+    /// for fields that synchronise early, which then repeat with the period of the energy levels.
+    fn cycle(&self) -> Cycle {
+        let mut field = self.clone();
+        // the copy restarts its step numbering, so earlier flares must not block flashes
+        for octo in field.octopuses.iter_mut().flatten() {
+            octo.last_flare = 0;
+        }
+        let mut seen: HashMap<Vec<usize>, usize> = HashMap::new();
+        let mut flashes: Vec<usize> = vec![0];
+        let mut s: usize = 0;
+        loop {
+            if let Some(start) = seen.insert(field.levels(), s) {
+                return Cycle {
+                    start,
+                    period: s - start,
+                    flashes,
+                };
             }
+            s += 1;
+            let flashed = field.step(s);
+            flashes.push(flashes[s - 1] + flashed);
+        }
+    }
+
+    /// returns how many flares there have been in this step
+    fn step(&mut self, s: usize) -> usize {
+        self.trace_step(s).len()
+    }
+
+    /// Performs a step and returns its flashes, ordered by wave and position.
+    fn trace_step(&mut self, s: usize) -> Vec<Flash> {
+        let mut flashes: Vec<Flash> = Vec::new();
+        // octopuses, which reached the threshold, with the neighbour that pushed them over it
+        let mut to_flare: HashMap<Point, Option<Point>> = HashMap::new();
+        self.octopuses.iter_mut().for_each(|line| {
+            line.iter_mut().for_each(|octo| {
+                if octo.increase() {
+                    to_flare.insert(octo.pos.clone(), None);
+                }
+            })
+        });
+        let mut wave: usize = 0;
+        while !to_flare.is_empty() {
+            let mut increase: Vec<(Point, Point)> = Vec::new();
+            to_flare
+                .into_iter()
+                .sorted_by_key(|(pt, _)| (pt.y, pt.x))
+                .for_each(|(pt, trigger)| {
+                    if self.octopuses[pt.y][pt.x].flare_at(s) {
+                        pt.neighbors(self.width, self.height, self.toroidal)
+                            .into_iter()
+                            .for_each(|neighbor| increase.push((neighbor, pt.clone())));
+                        flashes.push(Flash {
+                            pos: pt,
+                            wave,
+                            trigger,
+                        });
+                    }
+                });
+            to_flare = HashMap::new();
+            increase.into_iter().for_each(|(octo, source)| {
+                if self.octopuses[octo.y][octo.x].increase() {
+                    to_flare.insert(octo, Some(source));
+                }
+            });
+            wave += 1;
+        }
+        self.octopuses.iter_mut().for_each(|line| {
+            line.iter_mut().for_each(|octo| {
+                octo.update_level();
+            })
         });
-        flared = !to_flare.is_empty();
+        flashes
     }
-    field.iter_mut().fold(0, |acc, line| {
-        line.iter_mut().fold(0, |acc_inner, octo| {
-            acc_inner + octo.update_level().unwrap_or(0)
-        }) + acc
-    })
 }
 
-fn print_field(field: &[Vec<Octopus>]) {
-    for line in field {
-        for octo in line {
-            print!("{}", octo);
+impl Field {
+    /// The field coloured by energy level with ANSI escape codes, flashing octopuses are highlighted.
+    fn ansi(&self) -> String {
+        self.octopuses.iter().fold(String::new(), |acc, line| {
+            line.iter().fold(acc, |acc, octo| match octo.level {
+                0 => acc + "\x1b[1;97m0",
+                level => acc + &format!("\x1b[0;38;5;{}m{}", 232 + 2 * level, level),
+            }) + "\x1b[0m\n"
+        })
+    }
+
+    /// The field as plain PGM image with one pixel per octopus. Flashing octopuses are white, all
+    /// others get brighter with their energy level.
+    fn pgm(&self) -> String {
+        let rows = self
+            .octopuses
+            .iter()
+            .map(|line| {
+                line.iter()
+                    .map(|octo| match octo.level {
+                        0 => 10,
+                        level => level,
+                    })
+                    .join(" ")
+            })
+            .join("\n");
+        format!("P2\n{} {}\n10\n{}\n", self.width, self.height, rows)
+    }
+}
+
+impl std::fmt::Display for Field {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for line in &self.octopuses {
+            for octo in line {
+                write!(f, "{}", octo)?;
+            }
+            writeln!(f)?;
         }
-        println!();
+        Ok(())
     }
 }
 
+/// The evolution of a field from its initial state into a cycle of states.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Cycle {
+    /// first step, whose resulting state repeats
+    start: usize,
+    /// number of steps until the state repeats
+    period: usize,
+    /// total number of flashes after each step until the first repetition
+    flashes: Vec<usize>,
+}
+
+impl Cycle {
+    /// Total number of flashes after the given number of steps, without simulating them.
+    fn flashes_after(&self, steps: usize) -> usize {
+        if steps < self.flashes.len() {
+            return self.flashes[steps];
+        }
+        let per_period = self.flashes[self.start + self.period] - self.flashes[self.start];
+        let periods = (steps - self.start) / self.period;
+        let rest = (steps - self.start) % self.period;
+        self.flashes[self.start + rest] + periods * per_period
+    }
+}
+
+/// A flash of an octopus during a step.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Flash {
+    pos: Point,
+    /// 0 for octopuses flashing on their own, otherwise one more than the wave of the trigger
+    wave: usize,
+    /// the neighbour whose flash pushed this octopus over the threshold
+    trigger: Option<Point>,
+}
+
+/// Writes the flashes of consecutive steps, starting with step 1, as a JSON array.
+fn trace_json(trace: &[Vec<Flash>]) -> String {
+    let point = |pt: &Point| format!("{{\"x\": {}, \"y\": {}}}", pt.x, pt.y);
+    let steps = trace
+        .iter()
+        .enumerate()
+        .map(|(idx, flashes)| {
+            let flashes = flashes
+                .iter()
+                .map(|flash| {
+                    format!(
+                        "      {{\"pos\": {}, \"wave\": {}, \"trigger\": {}}}",
+                        point(&flash.pos),
+                        flash.wave,
+                        flash
+                            .trigger
+                            .as_ref()
+                            .map(point)
+                            .unwrap_or_else(|| "null".to_string())
+                    )
+                })
+                .join(",\n");
+            if flashes.is_empty() {
+                format!("  {{\"step\": {}, \"flashes\": []}}", idx + 1)
+            } else {
+                format!(
+                    "  {{\"step\": {}, \"flashes\": [\n{}\n  ]}}",
+                    idx + 1,
+                    flashes
+                )
+            }
+        })
+        .join(",\n");
+    format!("[\n{}\n]\n", steps)
+}
+
+/// Writes the flashes of consecutive steps, starting with step 1, as a DOT graph with one cluster
+/// per step and an edge from each trigger to the flash it caused.
+fn trace_dot(trace: &[Vec<Flash>]) -> String {
+    let node = |s: usize, pt: &Point| format!("s{}_{}_{}", s, pt.x, pt.y);
+    let mut result = String::from("digraph cascade {\n");
+    trace.iter().enumerate().for_each(|(idx, flashes)| {
+        let s = idx + 1;
+        result += &format!("  subgraph cluster_{} {{\n    label=\"step {}\";\n", s, s);
+        flashes.iter().for_each(|flash| {
+            result += &format!(
+                "    {} [label=\"{},{}\\nwave {}\"];\n",
+                node(s, &flash.pos),
+                flash.pos.x,
+                flash.pos.y,
+                flash.wave
+            );
+        });
+        flashes.iter().for_each(|flash| {
+            if let Some(trigger) = &flash.trigger {
+                result += &format!("    {} -> {};\n", node(s, trigger), node(s, &flash.pos));
+            }
+        });
+        result += "  }\n";
+    });
+    result += "}\n";
+    result
+}
+
 #[derive(Debug, Clone)]
 struct Octopus {
     level: usize,
@@ -116,13 +398,14 @@ impl Octopus {
         self.level == 10
     }
 
-    fn flare_at(&mut self, s: usize) -> Vec<Point> {
-        let mut result = Vec::new();
+    /// returns whether the octopus has not flared in this step yet
+    fn flare_at(&mut self, s: usize) -> bool {
         if self.last_flare != s {
             self.last_flare = s;
-            result.append(&mut self.pos.neighbors());
+            true
+        } else {
+            false
         }
-        result
     }
 
     fn update_level(&mut self) -> Option<usize> {
@@ -135,60 +418,47 @@ impl Octopus {
     }
 }
 
-#[derive(Debug, Clone, Hash, PartialEq, Eq)]
+#[derive(Debug, Clone, Default, Hash, PartialEq, Eq)]
 struct Point {
     x: usize,
     y: usize,
-    neighbor_cache: Option<Vec<Point>>,
 }
 
-impl Default for Point {
-    fn default() -> Self {
-        Self {
-            x: 0,
-            y: 0,
-            neighbor_cache: None,
-        }
-    }
-}
 impl Point {
     fn new(x: usize, y: usize) -> Self {
-        Self {
-            x,
-            y,
-            neighbor_cache: None,
-        }
+        Self { x, y }
     }
 
-    fn neighbors(&self) -> Vec<Point> {
-        if self.neighbor_cache.is_some() {
-            return self.neighbor_cache.clone().unwrap();
-        }
-        let mut result: Vec<Point> = Vec::new();
-        let mut xvals: Vec<usize> = Vec::new();
-        let mut yvals: Vec<usize> = Vec::new();
-        xvals.push(self.x);
-        yvals.push(self.y);
-        if self.x < 9 {
-            xvals.push(self.x + 1);
-        }
-        if self.x > 0 {
-            xvals.push(self.x - 1);
-        }
-        if self.y < 9 {
-            yvals.push(self.y + 1);
-        }
-        if self.y > 0 {
-            yvals.push(self.y - 1);
+    /// Coordinates next to `val` (including itself) on an axis of the given length.
+    fn adjacent(val: usize, len: usize, toroidal: bool) -> Vec<usize> {
+        let mut result: Vec<usize> = vec![val];
+        if toroidal {
+            [(val + 1) % len, (val + len - 1) % len]
+                .into_iter()
+                .for_each(|elem| {
+                    if !result.contains(&elem) {
+                        result.push(elem);
+                    }
+                });
+        } else {
+            if val + 1 < len {
+                result.push(val + 1);
+            }
+            if val > 0 {
+                result.push(val - 1);
+            }
         }
-        xvals
-            .iter()
-            .cartesian_product(yvals.iter())
-            .for_each(|elem| {
-                result.push(Point::new(*elem.0, *elem.1));
-            });
         result
     }
+
+    /// All points around this one (including itself) in a field of the given size.
+    fn neighbors(&self, width: usize, height: usize, toroidal: bool) -> Vec<Point> {
+        Self::adjacent(self.x, width, toroidal)
+            .into_iter()
+            .cartesian_product(Self::adjacent(self.y, height, toroidal))
+            .map(|(x, y)| Point::new(x, y))
+            .collect()
+    }
 }
 
 fn digit_value(input: &str) -> IResult<&str, usize> {
@@ -213,3 +483,139 @@ fn octo_line(input: &str) -> IResult<&str, Vec<Octopus>> {
 fn octo_array(input: &str) -> Vec<Vec<Octopus>> {
     many1(terminated(octo_line, line_ending))(input).unwrap().1
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn field(input: &str, toroidal: bool) -> Field {
+        Field::new(octo_array(input), toroidal).unwrap()
+    }
+
+    fn synchronised(mut field: Field) -> usize {
+        (1..).find(|s| field.step(*s) == field.len()).unwrap()
+    }
+
+    const EXAMPLE: &str = "5483143223
+2745854711
+5264556173
+6141336146
+6357385478
+4167524645
+2176841721
+6882881134
+4846848554
+5283751526
+";
+
+    #[test]
+    fn example() {
+        let mut octopuses = field(EXAMPLE, false);
+        assert_eq!(synchronised(octopuses.clone()), 195);
+        assert_eq!((1..101).map(|s| octopuses.step(s)).sum::<usize>(), 1656);
+    }
+
+    #[test]
+    fn small_example() {
+        let mut octopuses = field("11111\n19991\n19191\n19991\n11111\n", false);
+        assert_eq!(octopuses.step(1), 9);
+        assert_eq!(octopuses.to_string(), "34543\n40004\n50005\n40004\n34543\n");
+        assert_eq!(octopuses.step(2), 0);
+        assert_eq!(octopuses.to_string(), "45654\n51115\n61116\n51115\n45654\n");
+    }
+
+    #[test]
+    fn rectangular() {
+        let mut octopuses = field("9000\n0000\n0009\n", false);
+        assert_eq!((octopuses.width, octopuses.height), (4, 3));
+        assert_eq!(octopuses.step(1), 2);
+        assert_eq!(octopuses.to_string(), "0211\n2222\n1120\n");
+        assert!(Field::new(octo_array("123\n45\n"), false).is_err());
+    }
+
+    #[test]
+    fn toroidal() {
+        let mut octopuses = field("9000\n0000\n0009\n", true);
+        assert_eq!(octopuses.step(1), 2);
+        // both corners are diagonally adjacent across the borders
+        assert_eq!(octopuses.to_string(), "0223\n3223\n3220\n");
+        assert_eq!(synchronised(field("98\n88\n", true)), 1);
+        assert_eq!(synchronised(field("98\n88\n", false)), 1);
+    }
+
+    #[test]
+    fn cycle() {
+        let octopuses = field(EXAMPLE, false);
+        let cycle = octopuses.cycle();
+        // all octopuses flash together from step 195 on
+        assert_eq!((cycle.start, cycle.period), (195, 10));
+        assert_eq!(cycle.flashes_after(10), 204);
+        assert_eq!(cycle.flashes_after(100), 1656);
+        let mut simulated = octopuses.clone();
+        let total = (1..=250).map(|s| simulated.step(s)).sum::<usize>();
+        assert_eq!(cycle.flashes_after(250), total);
+        assert_eq!(
+            cycle.flashes_after(1_000_000_000_000),
+            cycle.flashes_after(195) + (1_000_000_000_000 - 195) / 10 * 100
+        );
+        // a field that has already stepped counts from its current state
+        let mut stepped = octopuses.clone();
+        let before = (1..=10).map(|s| stepped.step(s)).sum::<usize>();
+        let cycle = stepped.cycle();
+        assert_eq!((cycle.start, cycle.period), (185, 10));
+        assert_eq!(before + cycle.flashes_after(90), 1656);
+        // the octopuses flared in step 1 and flash again in the first step of the copy
+        let mut synced = field("98\n88\n", false);
+        assert_eq!(synced.step(1), 4);
+        assert_eq!((2..=10).map(|s| synced.step(s)).sum::<usize>(), 0);
+        let cycle = synced.cycle();
+        assert_eq!((cycle.start, cycle.period), (0, 10));
+        assert_eq!(cycle.flashes_after(1), 4);
+    }
+
+    #[test]
+    fn trace() {
+        let mut octopuses = field("900\n080\n000\n", false);
+        let flashes = octopuses.trace_step(1);
+        assert_eq!(
+            flashes,
+            vec![
+                Flash {
+                    pos: Point::new(0, 0),
+                    wave: 0,
+                    trigger: None
+                },
+                Flash {
+                    pos: Point::new(1, 1),
+                    wave: 1,
+                    trigger: Some(Point::new(0, 0))
+                },
+            ]
+        );
+        assert_eq!(octopuses.to_string(), "032\n302\n222\n");
+        let trace = vec![flashes, Vec::new()];
+        assert_eq!(
+            trace_json(&trace),
+            "[
+  {\"step\": 1, \"flashes\": [
+      {\"pos\": {\"x\": 0, \"y\": 0}, \"wave\": 0, \"trigger\": null},
+      {\"pos\": {\"x\": 1, \"y\": 1}, \"wave\": 1, \"trigger\": {\"x\": 0, \"y\": 0}}
+  ]},
+  {\"step\": 2, \"flashes\": []}
+]
+"
+        );
+        assert!(trace_dot(&trace).contains("    s1_0_0 -> s1_1_1;\n"));
+    }
+
+    #[test]
+    fn frames() {
+        let octopuses = field("905\n120\n", false);
+        assert_eq!(octopuses.pgm(), "P2\n3 2\n10\n9 10 5\n1 2 10\n");
+        assert_eq!(
+            octopuses.ansi(),
+            "\x1b[0;38;5;250m9\x1b[1;97m0\x1b[0;38;5;242m5\x1b[0m\n\
+             \x1b[0;38;5;234m1\x1b[0;38;5;236m2\x1b[1;97m0\x1b[0m\n"
+        );
+    }
+}
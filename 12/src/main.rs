@@ -1,171 +1,853 @@
 use std::{
-    cell::RefCell,
-    collections::HashMap,
-    rc::{Rc, Weak},
-    sync::atomic::{AtomicBool, AtomicUsize, Ordering},
+    cmp::Reverse,
+    collections::{BinaryHeap, HashMap, HashSet},
 };
 
 use nom::{
-    bytes::complete::{tag, take_while_m_n},
-    character::{
-        complete::{alpha1, char, line_ending, one_of},
-        is_digit,
-    },
-    combinator::{map_res, recognize},
-    multi::{many0, many1, separated_list0, separated_list1},
-    sequence::{separated_pair, terminated},
+    character::complete::{alpha1, char, line_ending, u64},
+    combinator::{map, opt},
+    multi::many1,
+    sequence::{preceded, separated_pair, terminated, tuple},
     IResult,
 };
 
 fn main() {
-    let input = std::fs::read_to_string(std::env::args().last().unwrap()).unwrap();
-
-    let (_, input_construct) = Parser::parse(&input).unwrap();
+    let args: Vec<String> = std::env::args().collect();
+    let input = std::fs::read_to_string(args.last().unwrap()).unwrap();
 
     let mut graph = Graph::default();
 
     Parser::parse(&input).unwrap().1.iter().for_each(|pair| {
-        graph.add_node(pair.0);
-        graph.add_node(pair.1);
-        graph.link_nodes(pair.0, pair.1);
+        graph.link_nodes(pair.0, pair.1, pair.2);
     });
 
-    let result = graph.paths();
-    println!("{}", result.len());
+    let values = |flag: &str| -> Vec<&String> {
+        args.windows(2)
+            .filter(|pair| pair[0] == flag)
+            .map(|pair| &pair[1])
+            .collect()
+    };
+    let mut policy = Policy::default();
+    if let Some(start) = values("--start").last() {
+        policy.start = start.to_string();
+    }
+    if let Some(end) = values("--end").last() {
+        policy.end = end.to_string();
+    }
+    for limit in values("--limit") {
+        match limit.split_once('=').map(|(cave, max)| (cave, max.parse())) {
+            Some((cave, Ok(max))) => {
+                policy.limits.insert(cave.to_string(), max);
+            }
+            _ => {
+                eprintln!("invalid limit '{}', expected <cave>=<visits>", limit);
+                std::process::exit(1);
+            }
+        }
+    }
+    policy.forbidden = values("--forbid").into_iter().cloned().collect();
+    policy.waypoints = values("--via").into_iter().cloned().collect();
+    let revisits: Vec<usize> = match values("--revisits").last() {
+        Some(revisits) => vec![revisits.parse().unwrap()],
+        None => vec![0, 1],
+    };
+    let count = args.iter().any(|arg| arg == "--count");
+
+    if args.iter().any(|arg| arg == "--print") {
+        let policy = Policy {
+            revisits: revisits[0],
+            ..policy
+        };
+        graph
+            .paths(&policy)
+            .unwrap_or_else(fail)
+            .iter()
+            .for_each(|path| println!("{}", graph.path_names(path)));
+        return;
+    }
 
-    let mut result2 = graph.paths2();
-    result2.sort_unstable();
-    result2.dedup();
+    if let Some(k) = values("--shortest").last() {
+        let policy = Policy {
+            revisits: revisits[0],
+            ..policy
+        };
+        graph
+            .shortest_paths(&policy, k.parse().unwrap())
+            .unwrap_or_else(fail)
+            .iter()
+            .for_each(|(cost, path)| println!("{}: {}", cost, graph.path_names(path)));
+        return;
+    }
+
+    if let Some(file) = values("--dot").last() {
+        let policy = Policy {
+            revisits: revisits[0],
+            ..policy
+        };
+        let highlight = match values("--highlight").last() {
+            Some(names) => graph.path_ids(names).unwrap_or_else(fail),
+            None => Vec::new(),
+        };
+        let usage = if args.iter().any(|arg| arg == "--usage") {
+            edge_usage(&graph.paths(&policy).unwrap_or_else(fail))
+        } else {
+            HashMap::new()
+        };
+        std::fs::write(file, graph.dot(&highlight, &usage)).unwrap();
+        return;
+    }
+
+    for revisits in revisits {
+        let policy = Policy {
+            revisits,
+            ..policy.clone()
+        };
+        let result = if count {
+            graph.count_paths(&policy)
+        } else {
+            graph.paths(&policy).map(|paths| paths.len())
+        };
+        println!("{}", result.unwrap_or_else(fail));
+    }
+}
+
+fn fail<T>(err: String) -> T {
+    eprintln!("{}", err);
+    std::process::exit(1);
+}
 
-    println!("{}", result2.len());
+/// Assigns consecutive ids, starting with 0, to names in the order they are first seen.
+#[derive(Debug, Clone, Default)]
+struct Interner {
+    ids: HashMap<String, usize>,
+    names: Vec<String>,
 }
 
+impl Interner {
+    fn intern(&mut self, name: &str) -> usize {
+        if let Some(id) = self.ids.get(name) {
+            return *id;
+        }
+        let id = self.names.len();
+        self.ids.insert(String::from(name), id);
+        self.names.push(String::from(name));
+        id
+    }
+
+    fn id(&self, name: &str) -> Option<usize> {
+        self.ids.get(name).cloned()
+    }
+
+    fn name(&self, id: usize) -> &str {
+        &self.names[id]
+    }
+}
+
+#[derive(Debug, Clone)]
 struct Node {
     nmbr: usize,
-    name: String,
-    neighbors: Vec<Weak<RefCell<Node>>>,
-    is_start: bool,
+    neighbors: Vec<usize>,
+    /// travel time through the tunnel to the neighbour at the same index
+    weights: Vec<usize>,
+    is_big: bool,
 }
 
 impl Node {
-    fn new(input: &str) -> Self {
-        static COUNTER: AtomicUsize = AtomicUsize::new(0);
+    fn new(nmbr: usize, name: &str) -> Self {
         Node {
-            nmbr: COUNTER.fetch_add(1, Ordering::Relaxed),
-            name: String::from(input),
+            nmbr,
             neighbors: Vec::new(),
-            is_start: input.eq("start"),
+            weights: Vec::new(),
+            is_big: name == name.to_uppercase(),
         }
     }
 
-    fn connect_to(&mut self, other_node: &Rc<RefCell<Node>>) {
-        self.neighbors.push(Rc::downgrade(other_node));
+    fn connect_to(&mut self, other: usize, weight: usize) {
+        self.neighbors.push(other);
+        self.weights.push(weight);
     }
+}
 
-    fn is_big(&self) -> bool {
-        self.name == self.name.to_uppercase()
-    }
+/// Rules for the caves a path may visit.
+///
+/// Small caves may be visited once and big caves arbitrarily often, unless `limits` says
+/// otherwise. The start is never visited twice and a path ends as soon as it reaches the end.
+#[derive(Debug, Clone)]
+struct Policy {
+    start: String,
+    end: String,
+    /// how many visits beyond the limit of a cave are allowed in total
+    revisits: usize,
+    /// maximal number of visits for single caves
+    limits: HashMap<String, usize>,
+    /// caves, which must not be visited at all
+    forbidden: HashSet<String>,
+    /// caves, which must all be visited before reaching the end
+    waypoints: Vec<String>,
+}
 
-    fn paths(&self, visited: &[usize]) -> Vec<Vec<usize>> {
-        let mut result = Vec::new();
-        if self.name == "end" {
-            result.push([visited, &[self.nmbr]].concat());
-            return result;
-        }
-        if self.is_big() || !visited.contains(&self.nmbr) {
-            self.neighbors.iter().for_each(|elem| {
-                let node = elem.upgrade().unwrap();
-                let mut inner_result = node.borrow().paths(&[visited, &[self.nmbr]].concat());
-                result.append(&mut inner_result);
-            });
+impl Default for Policy {
+    fn default() -> Self {
+        Self {
+            start: String::from("start"),
+            end: String::from("end"),
+            revisits: 0,
+            limits: HashMap::new(),
+            forbidden: HashSet::new(),
+            waypoints: Vec::new(),
         }
-        result
     }
+}
 
-    fn paths2(&self, visited: &[usize], double_visit: bool) -> Vec<Vec<usize>> {
-        let mut result = Vec::new();
-        if self.name == "end" {
-            result.push([visited, &[self.nmbr]].concat());
-            return result;
-        }
-        let cont = visited.contains(&self.nmbr);
-        if self.is_big() || !cont || double_visit {
-            self.neighbors.iter().for_each(|elem| {
-                let node = elem.upgrade().unwrap();
-                if self.is_big() || !cont {
-                    let mut inner_result = node
-                        .borrow()
-                        .paths2(&[visited, &[self.nmbr]].concat(), double_visit);
-                    result.append(&mut inner_result);
-                }
-                if double_visit && !self.is_big() && cont && !self.is_start {
-                    let mut inner_result = node.borrow().paths(&[visited, &[self.nmbr]].concat());
-                    result.append(&mut inner_result);
-                }
-            });
+impl Policy {
+    /// Resolves the cave names of the policy against the graph.
+    fn rules(&self, graph: &Graph) -> Result<Rules, String> {
+        let id = |name: &str| graph.id(name).ok_or(format!("unknown cave {}", name));
+        let mut limits: Vec<Option<usize>> = graph
+            .nodes
+            .iter()
+            .map(|node| (!node.is_big).then_some(1))
+            .collect();
+        for (name, limit) in &self.limits {
+            limits[id(name)?] = Some(*limit);
         }
-        result
+        let mut forbidden = vec![false; graph.nodes.len()];
+        for name in &self.forbidden {
+            forbidden[id(name)?] = true;
+        }
+        let mut waypoint = vec![false; graph.nodes.len()];
+        for name in &self.waypoints {
+            waypoint[id(name)?] = true;
+        }
+        let start = id(&self.start)?;
+        let end = id(&self.end)?;
+        // each cave, whose visits matter for the remaining path, gets a field of the memo key
+        let mut fields = Vec::new();
+        let mut shift: u32 = 0;
+        for idx in 0..graph.nodes.len() {
+            let max = match limits[idx] {
+                _ if forbidden[idx] || idx == end => 0,
+                _ if idx == start => 1,
+                Some(limit) => std::cmp::max(limit, usize::from(waypoint[idx])),
+                None => usize::from(waypoint[idx]),
+            };
+            let width = usize::BITS - max.leading_zeros();
+            fields.push((shift, width));
+            shift += width;
+        }
+        let rules = Rules {
+            start,
+            end,
+            revisits: self.revisits,
+            limits,
+            forbidden,
+            waypoint,
+            fields,
+        };
+        // a path may bounce between two unlimited caves forever
+        let unlimited = |id: usize| {
+            rules.limits[id].is_none()
+                && !rules.forbidden[id]
+                && id != rules.start
+                && id != rules.end
+        };
+        for node in graph.nodes.iter().filter(|node| unlimited(node.nmbr)) {
+            if let Some(other) = node.neighbors.iter().find(|elem| unlimited(**elem)) {
+                return Err(format!(
+                    "caves {} and {} are connected and unlimited, so there are infinitely many paths",
+                    graph.name(node.nmbr),
+                    graph.name(*other)
+                ));
+            }
+        }
+        Ok(rules)
     }
 }
 
-struct Graph {
-    store: HashMap<String, Rc<RefCell<Node>>>,
+/// A [Policy] with the caves resolved to node ids.
+#[derive(Debug, Clone)]
+struct Rules {
+    start: usize,
+    end: usize,
+    revisits: usize,
+    limits: Vec<Option<usize>>,
+    forbidden: Vec<bool>,
+    waypoint: Vec<bool>,
+    /// shift and width of the visits of each cave in the key
+    fields: Vec<(u32, u32)>,
 }
 
-impl Default for Graph {
-    fn default() -> Self {
-        Self {
-            store: HashMap::new(),
+impl Rules {
+    /// Enters the node with the given number of revisits left. Returns the revisits left
+    /// afterwards, or [None] if the node may not be entered.
+    fn enter(&self, id: usize, visits: &mut [usize], revisits: usize) -> Option<usize> {
+        if self.forbidden[id] || (id == self.start && visits[id] > 0) {
+            return None;
         }
+        let revisits = match self.limits[id] {
+            Some(limit) if visits[id] >= limit => {
+                if revisits == 0 || id == self.start || id == self.end {
+                    return None;
+                }
+                revisits - 1
+            }
+            _ => revisits,
+        };
+        visits[id] += 1;
+        Some(revisits)
     }
+
+    fn complete(&self, visits: &[usize]) -> bool {
+        visits
+            .iter()
+            .zip(&self.waypoint)
+            .all(|(visits, waypoint)| !waypoint || *visits > 0)
+    }
+
+    /// Number of bits of a key.
+    fn key_bits(&self) -> u32 {
+        self.fields.iter().map(|(_, width)| width).sum()
+    }
+
+    /// The part of the visits, which matters for the remaining path, packed into the fields of a
+    /// key. Visits beyond the limit of a cave cost a revisit each, whatever their number, so they
+    /// are stored as the limit, and small caves take a single bit. Unlimited caves take a bit
+    /// only as waypoints, where any visit counts.
+    fn key(&self, visits: &[usize]) -> u64 {
+        visits
+            .iter()
+            .zip(&self.fields)
+            .enumerate()
+            .filter(|(_, (_, (_, width)))| *width > 0)
+            .fold(0, |acc, (id, (visits, (shift, _)))| {
+                let visits = match self.limits[id] {
+                    Some(limit) => std::cmp::min(*visits, std::cmp::max(limit, 1)),
+                    None => std::cmp::min(*visits, 1),
+                };
+                acc | (visits as u64) << shift
+            })
+    }
+}
+
+/// Cave system stored as an arena of nodes, which refer to their neighbours by id.
+#[derive(Debug, Clone, Default)]
+struct Graph {
+    names: Interner,
+    nodes: Vec<Node>,
 }
 
 impl Graph {
-    fn add_node(&mut self, name: &str) {
-        self.store
-            .entry(String::from(name))
-            .or_insert_with(|| Rc::new(RefCell::new(Node::new(name))));
+    /// Returns the id of the node with the given name, which is added if it does not exist yet.
+    fn add_node(&mut self, name: &str) -> usize {
+        let id = self.names.intern(name);
+        if id == self.nodes.len() {
+            self.nodes.push(Node::new(id, name));
+        }
+        id
+    }
+
+    fn link_nodes(&mut self, name1: &str, name2: &str, weight: usize) {
+        let x = self.add_node(name1);
+        let y = self.add_node(name2);
+        self.nodes[x].connect_to(y, weight);
+        if x != y {
+            self.nodes[y].connect_to(x, weight);
+        }
+    }
+
+    fn id(&self, name: &str) -> Option<usize> {
+        self.names.id(name)
+    }
+
+    fn name(&self, id: usize) -> &str {
+        self.names.name(id)
+    }
+
+    fn neighbors(&self, id: usize) -> &[usize] {
+        &self.nodes[id].neighbors
+    }
+
+    /// Cave names of the path separated by commas.
+    fn path_names(&self, path: &[usize]) -> String {
+        path.iter()
+            .map(|id| self.name(*id))
+            .collect::<Vec<_>>()
+            .join(",")
     }
 
-    fn link_nodes(&mut self, name1: &str, name2: &str) {
-        if let Some(x) = self.store.get(&String::from(name1)) {
-            if let Some(y) = self.store.get(&String::from(name2)) {
-                x.borrow_mut().connect_to(y);
-                y.borrow_mut().connect_to(x);
+    /// Parses a path of comma separated cave names, where consecutive caves need to be connected.
+    fn path_ids(&self, names: &str) -> Result<Vec<usize>, String> {
+        let path = names
+            .split(',')
+            .map(|name| self.id(name).ok_or(format!("unknown cave {}", name)))
+            .collect::<Result<Vec<usize>, String>>()?;
+        match path
+            .windows(2)
+            .find(|pair| !self.neighbors(pair[0]).contains(&pair[1]))
+        {
+            Some(pair) => Err(format!(
+                "caves {} and {} are not connected",
+                self.name(pair[0]),
+                self.name(pair[1])
+            )),
+            None => Ok(path),
+        }
+    }
+
+    /// The graph in DOT format, where big caves are boxes and small caves ellipses. The caves and
+    /// tunnels of `highlight` are emphasised, and tunnels are coloured and labelled by `usage`.
+    fn dot(&self, highlight: &[usize], usage: &HashMap<(usize, usize), usize>) -> String {
+        let highlighted: HashSet<(usize, usize)> = edges(highlight).collect();
+        let max = usage.values().max().cloned().unwrap_or(0);
+        let mut result = String::from("graph caves {\n");
+        self.nodes.iter().for_each(|node| {
+            let mut attributes = vec![if node.is_big {
+                "shape=box"
+            } else {
+                "shape=ellipse"
+            }];
+            if highlight.contains(&node.nmbr) {
+                attributes.push("style=filled");
+                attributes.push("fillcolor=lightblue");
             }
+            result += &format!(
+                "  \"{}\" [{}];\n",
+                self.name(node.nmbr),
+                attributes.join(", ")
+            );
+        });
+        self.nodes.iter().for_each(|node| {
+            node.neighbors
+                .iter()
+                .filter(|elem| node.nmbr <= **elem)
+                .for_each(|elem| {
+                    let edge = (node.nmbr, *elem);
+                    let mut attributes = Vec::new();
+                    let used = usage.get(&edge).cloned().unwrap_or(0);
+                    if let Some(level) = (used * 8).checked_div(max) {
+                        attributes.push(String::from("colorscheme=ylorrd9"));
+                        attributes.push(format!("color={}", 1 + level));
+                        attributes.push(format!("label={}", used));
+                    } else if highlighted.contains(&edge) {
+                        attributes.push(String::from("color=red"));
+                    }
+                    if highlighted.contains(&edge) {
+                        attributes.push(String::from("penwidth=3"));
+                    }
+                    result +=
+                        &format!("  \"{}\" -- \"{}\"", self.name(node.nmbr), self.name(*elem));
+                    if !attributes.is_empty() {
+                        result += &format!(" [{}]", attributes.join(", "));
+                    }
+                    result += ";\n";
+                });
+        });
+        result += "}\n";
+        result
+    }
+
+    /// The `k` cheapest paths from start to end by total travel time, which follow the policy.
+    /// Paths of the same cost are ordered by their caves.
+    fn shortest_paths(
+        &self,
+        policy: &Policy,
+        k: usize,
+    ) -> Result<Vec<(usize, Vec<usize>)>, String> {
+        let rules = policy.rules(self)?;
+        let mut result = Vec::new();
+        let mut visits = vec![0; self.nodes.len()];
+        let mut queue = BinaryHeap::new();
+        if let Some(revisits) = rules.enter(rules.start, &mut visits, rules.revisits) {
+            queue.push(Reverse((0, vec![rules.start], visits, revisits)));
+        }
+        // all weights are non-negative, so paths are completed in the order of their cost
+        while let Some(Reverse((cost, path, visits, revisits))) = queue.pop() {
+            if result.len() == k {
+                break;
+            }
+            let node = &self.nodes[*path.last().unwrap()];
+            if node.nmbr == rules.end {
+                if rules.complete(&visits) {
+                    result.push((cost, path));
+                }
+                continue;
+            }
+            node.neighbors
+                .iter()
+                .zip(&node.weights)
+                .for_each(|(elem, weight)| {
+                    let mut visits = visits.clone();
+                    if let Some(revisits) = rules.enter(*elem, &mut visits, revisits) {
+                        let path = [&path[..], &[*elem]].concat();
+                        queue.push(Reverse((cost + weight, path, visits, revisits)));
+                    }
+                });
         }
+        Ok(result)
     }
 
-    fn paths(&self) -> Vec<Vec<usize>> {
-        self.store
-            .get("start")
-            .map(|node| node.borrow().paths(&[]))
-            .unwrap()
+    /// All paths from start to end, which follow the policy.
+    fn paths(&self, policy: &Policy) -> Result<Vec<Vec<usize>>, String> {
+        let rules = policy.rules(self)?;
+        let mut result = Vec::new();
+        self.paths_from(
+            rules.start,
+            &rules,
+            &mut Vec::new(),
+            &mut vec![0; self.nodes.len()],
+            rules.revisits,
+            &mut result,
+        );
+        Ok(result)
     }
 
-    fn paths2(&self) -> Vec<Vec<usize>> {
-        self.store
-            .get("start")
-            .map(|node| node.borrow().paths2(&[], true))
-            .unwrap()
+    fn paths_from(
+        &self,
+        id: usize,
+        rules: &Rules,
+        path: &mut Vec<usize>,
+        visits: &mut [usize],
+        revisits: usize,
+        result: &mut Vec<Vec<usize>>,
+    ) {
+        let Some(revisits) = rules.enter(id, visits, revisits) else {
+            return;
+        };
+        path.push(id);
+        if id == rules.end {
+            if rules.complete(visits) {
+                result.push(path.clone());
+            }
+        } else {
+            self.neighbors(id).iter().for_each(|elem| {
+                self.paths_from(*elem, rules, path, visits, revisits, result);
+            });
+        }
+        path.pop();
+        visits[id] -= 1;
+    }
+
+    /// Counts the paths from start to end, which follow the policy, without enumerating them.
+    fn count_paths(&self, policy: &Policy) -> Result<usize, String> {
+        let rules = policy.rules(self)?;
+        if rules.key_bits() > u64::BITS {
+            return Err(format!(
+                "counting needs {} bits for the visits of the policy, at most {} are supported",
+                rules.key_bits(),
+                u64::BITS
+            ));
+        }
+        Ok(self.count_from(
+            rules.start,
+            &rules,
+            &mut vec![0; self.nodes.len()],
+            rules.revisits,
+            &mut HashMap::new(),
+        ))
+    }
+
+    /// Number of paths from the node to the end, memoized over the relevant visits before and the
+    /// revisits left.
+    fn count_from(
+        &self,
+        id: usize,
+        rules: &Rules,
+        visits: &mut [usize],
+        revisits: usize,
+        memo: &mut HashMap<(usize, u64, usize), usize>,
+    ) -> usize {
+        let Some(revisits) = rules.enter(id, visits, revisits) else {
+            return 0;
+        };
+        let count = if id == rules.end {
+            usize::from(rules.complete(visits))
+        } else {
+            let key = (id, rules.key(visits), revisits);
+            match memo.get(&key) {
+                Some(count) => *count,
+                None => {
+                    let count = self
+                        .neighbors(id)
+                        .iter()
+                        .map(|elem| self.count_from(*elem, rules, visits, revisits, memo))
+                        .sum();
+                    memo.insert(key, count);
+                    count
+                }
+            }
+        };
+        visits[id] -= 1;
+        count
     }
 }
 
+/// The tunnels along the path, with the smaller id first.
+fn edges(path: &[usize]) -> impl Iterator<Item = (usize, usize)> + '_ {
+    path.windows(2).map(|pair| {
+        (
+            std::cmp::min(pair[0], pair[1]),
+            std::cmp::max(pair[0], pair[1]),
+        )
+    })
+}
+
+/// How many of the paths pass through each tunnel, a path passing a tunnel twice counts once.
+fn edge_usage(paths: &[Vec<usize>]) -> HashMap<(usize, usize), usize> {
+    let mut usage = HashMap::new();
+    paths.iter().for_each(|path| {
+        edges(path)
+            .collect::<HashSet<_>>()
+            .into_iter()
+            .for_each(|edge| *usage.entry(edge).or_insert(0) += 1);
+    });
+    usage
+}
+
 struct Parser {}
 impl Parser {
     fn name(input: &str) -> IResult<&str, &str> {
         alpha1(input)
     }
 
-    fn connection(input: &str) -> IResult<&str, (&str, &str)> {
-        separated_pair(
-            Parser::name,
-            nom::character::complete::char('-'),
-            Parser::name,
+    /// A tunnel `a-b` with an optional travel time `a-b:5`, which defaults to 1.
+    fn connection(input: &str) -> IResult<&str, (&str, &str, usize)> {
+        map(
+            tuple((
+                separated_pair(Parser::name, char('-'), Parser::name),
+                opt(preceded(char(':'), u64)),
+            )),
+            |((name1, name2), weight)| (name1, name2, weight.unwrap_or(1) as usize),
         )(input)
     }
 
-    fn parse(input: &str) -> IResult<&str, Vec<(&str, &str)>> {
+    fn parse(input: &str) -> IResult<&str, Vec<(&str, &str, usize)>> {
         many1(terminated(Parser::connection, line_ending))(input)
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const EXAMPLE: &str = "start-A
+start-b
+A-c
+A-b
+b-d
+A-end
+b-end
+";
+
+    fn graph(input: &str) -> Graph {
+        let mut graph = Graph::default();
+        Parser::parse(input)
+            .unwrap()
+            .1
+            .iter()
+            .for_each(|pair| graph.link_nodes(pair.0, pair.1, pair.2));
+        graph
+    }
+
+    fn policy(revisits: usize) -> Policy {
+        Policy {
+            revisits,
+            ..Policy::default()
+        }
+    }
+
+    #[test]
+    fn example() {
+        let graph = graph(EXAMPLE);
+        assert_eq!(graph.paths(&policy(0)).unwrap().len(), 10);
+        assert_eq!(graph.paths(&policy(1)).unwrap().len(), 36);
+    }
+
+    #[test]
+    fn arena() {
+        let first = graph(EXAMPLE);
+        let second = graph(EXAMPLE);
+        assert_eq!(first.id("start"), Some(0));
+        assert_eq!(second.id("start"), Some(0));
+        assert_eq!(second.id("end"), Some(5));
+        assert_eq!(second.id("x"), None);
+        let mut copy = second.clone();
+        copy.link_nodes("d", "end", 1);
+        assert_eq!(copy.neighbors(4), &[2, 5]);
+        assert_eq!(second.neighbors(4), &[2]);
+        assert_eq!(copy.paths(&policy(0)).unwrap().len(), 13);
+    }
+
+    #[test]
+    fn count() {
+        for (input, paths, paths2) in [
+            (EXAMPLE, 10, 36),
+            (include_str!("../exa2.txt"), 19, 103),
+            (include_str!("../exa3.txt"), 226, 3509),
+        ] {
+            let graph = graph(input);
+            assert_eq!(graph.count_paths(&policy(0)), Ok(paths));
+            assert_eq!(graph.count_paths(&policy(1)), Ok(paths2));
+        }
+        let mut graph = graph(EXAMPLE);
+        graph.link_nodes("A", "B", 1);
+        assert!(graph.count_paths(&policy(0)).is_err());
+    }
+
+    #[test]
+    fn policies() {
+        let graph = graph(include_str!("../exa2.txt"));
+        for policy in [
+            policy(2),
+            Policy {
+                waypoints: vec![String::from("kj"), String::from("HN")],
+                ..policy(1)
+            },
+            Policy {
+                forbidden: HashSet::from([String::from("dc")]),
+                ..policy(1)
+            },
+            Policy {
+                limits: HashMap::from([(String::from("LN"), 1), (String::from("sa"), 3)]),
+                ..policy(0)
+            },
+            Policy {
+                start: String::from("kj"),
+                end: String::from("sa"),
+                ..policy(1)
+            },
+        ] {
+            let paths = graph.paths(&policy).unwrap();
+            assert_eq!(graph.count_paths(&policy), Ok(paths.len()));
+            let rules = policy.rules(&graph).unwrap();
+            for path in paths {
+                assert_eq!(path.first(), Some(&rules.start));
+                assert_eq!(path.last(), Some(&rules.end));
+                assert!(path.iter().all(|id| !rules.forbidden[*id]));
+                assert!(policy
+                    .waypoints
+                    .iter()
+                    .all(|name| path.contains(&graph.id(name).unwrap())));
+            }
+        }
+        let only_once = Policy {
+            forbidden: HashSet::from([String::from("dc"), String::from("kj")]),
+            ..policy(0)
+        };
+        assert_eq!(graph.count_paths(&only_once), Ok(1));
+        assert!(graph
+            .count_paths(&Policy {
+                waypoints: vec![String::from("xy")],
+                ..policy(0)
+            })
+            .is_err());
+    }
+
+    #[test]
+    fn dot() {
+        let graph = graph(EXAMPLE);
+        let paths = graph.paths(&policy(0)).unwrap();
+        assert_eq!(graph.path_names(&paths[0]), "start,A,c,A,b,A,end");
+        let path = graph.path_ids("start,b,end").unwrap();
+        assert_eq!(graph.path_names(&path), "start,b,end");
+        assert!(graph.path_ids("start,c").is_err());
+        assert!(graph.path_ids("start,x").is_err());
+
+        let plain = graph.dot(&[], &HashMap::new());
+        assert!(plain.starts_with("graph caves {\n  \"start\" [shape=ellipse];\n"));
+        assert!(plain.contains("  \"A\" [shape=box];\n"));
+        assert!(plain.contains("  \"start\" -- \"A\";\n"));
+        assert_eq!(plain.matches(" -- ").count(), 7);
+
+        let highlighted = graph.dot(&path, &HashMap::new());
+        assert!(
+            highlighted.contains("  \"b\" [shape=ellipse, style=filled, fillcolor=lightblue];\n")
+        );
+        assert!(highlighted.contains("  \"start\" -- \"b\" [color=red, penwidth=3];\n"));
+
+        let usage = edge_usage(&paths);
+        // every path starts through A or b
+        assert_eq!(usage[&(0, 1)] + usage[&(0, 2)], 10);
+        // start,A,c,A,b,A,end passes A-c twice, but is a single path using it
+        let (a, c) = (graph.id("A").unwrap(), graph.id("c").unwrap());
+        let using = paths
+            .iter()
+            .filter(|path| edges(path).any(|edge| edge == (a, c)))
+            .count();
+        assert_eq!(using, 5);
+        assert_eq!(usage[&(a, c)], using);
+        let coloured = graph.dot(&[], &usage);
+        assert!(coloured.contains("  \"b\" -- \"d\" [colorscheme=ylorrd9, color=1, label=0];\n"));
+    }
+
+    #[test]
+    fn weights() {
+        let graph = graph("start-A:2\nstart-b:4\nA-c:1\nA-b:1\nb-d:7\nA-end:9\nb-end:3\n");
+        let paths = graph.shortest_paths(&policy(0), 4).unwrap();
+        let paths: Vec<(usize, String)> = paths
+            .iter()
+            .map(|(cost, path)| (*cost, graph.path_names(path)))
+            .collect();
+        assert_eq!(
+            paths,
+            vec![
+                (6, String::from("start,A,b,end")),
+                (7, String::from("start,b,end")),
+                (8, String::from("start,A,c,A,b,end")),
+                (11, String::from("start,A,end")),
+            ]
+        );
+        let all = graph.shortest_paths(&policy(1), usize::MAX).unwrap();
+        assert_eq!(all.len(), 36);
+        assert!(all.windows(2).all(|pair| pair[0].0 <= pair[1].0));
+
+        let between = Policy {
+            start: String::from("d"),
+            end: String::from("c"),
+            ..policy(0)
+        };
+        assert_eq!(
+            graph.shortest_paths(&between, 1).unwrap(),
+            vec![(9, graph.path_ids("d,b,A,c").unwrap())]
+        );
+        assert_eq!(Parser::connection("a-b:12"), Ok(("", ("a", "b", 12))));
+        assert_eq!(Parser::connection("a-b\n"), Ok(("\n", ("a", "b", 1))));
+    }
+
+    #[test]
+    fn packed_key() {
+        // start and a chain of 63 small caves fill all 64 bits
+        let mut chain = Graph::default();
+        chain.link_nodes("start", "c0", 1);
+        (1..63).for_each(|idx| chain.link_nodes(&format!("c{}", idx - 1), &format!("c{}", idx), 1));
+        chain.link_nodes("c62", "end", 1);
+        assert_eq!(chain.count_paths(&policy(0)), Ok(1));
+        let rules = policy(0).rules(&chain).unwrap();
+        assert_eq!(rules.fields[rules.end], (64, 0));
+        // capped at their limit, small caves take a single bit with revisits as well
+        assert_eq!(policy(1).rules(&chain).unwrap().key_bits(), 64);
+        assert_eq!(
+            chain.count_paths(&policy(1)),
+            Ok(chain.paths(&policy(1)).unwrap().len())
+        );
+        chain.link_nodes("c62", "c63", 1);
+        assert!(chain.count_paths(&policy(1)).is_err());
+        // only counting needs the key, the dead end c63 can be left with the revisit of c62
+        assert_eq!(chain.paths(&policy(1)).unwrap().len(), 2);
+        assert!(chain.shortest_paths(&policy(1), 1).is_ok());
+
+        let graph = graph(EXAMPLE);
+        let limits = Policy {
+            limits: HashMap::from([(String::from("b"), 3)]),
+            waypoints: vec![String::from("A")],
+            ..policy(1)
+        };
+        let rules = limits.rules(&graph).unwrap();
+        // b is stored up to its limit of 3, A is a waypoint
+        assert_eq!(rules.fields[graph.id("b").unwrap()].1, 2);
+        assert_eq!(rules.fields[graph.id("A").unwrap()].1, 1);
+        assert_eq!(
+            graph.count_paths(&limits),
+            Ok(graph.paths(&limits).unwrap().len())
+        );
+    }
+}
@@ -1,167 +1,253 @@
-use std::error::Error;
+use std::collections::HashSet;
 
 use nom::{
-    branch::alt,
-    bytes::complete::{tag, take_while_m_n},
-    character::{
-        complete::{alpha1, char, line_ending, multispace0, one_of},
-        is_digit,
-    },
+    bytes::complete::tag,
+    character::complete::{alpha1, line_ending, one_of},
     combinator::{map_res, recognize},
-    error::ErrorKind,
-    multi::{many0, many1, separated_list0, separated_list1},
-    sequence::{preceded, separated_pair, terminated, tuple},
+    multi::many1,
+    sequence::{preceded, terminated, tuple},
     IResult,
 };
 
 fn main() {
-    let input = std::fs::read_to_string(std::env::args().last().unwrap()).unwrap();
+    let args: Vec<String> = std::env::args().collect();
+    let input = std::fs::read_to_string(args.last().unwrap()).unwrap();
     let (page_desc, instructions) = Parser::parse(&input).unwrap().1;
 
-    println!("{:?}", page_desc);
-    let mut page = create_page(&page_desc);
-    print_page(&page);
-    println!("{:?}", instructions);
+    let page = Page::new(&page_desc);
+
+    if let Some(pos) = args.iter().position(|arg| arg == "--frames") {
+        let dir = std::path::Path::new(&args[pos + 1]);
+        let pbm = args.iter().any(|arg| arg == "--pbm");
+        let overlay = args.iter().any(|arg| arg == "--overlay");
+        std::fs::create_dir_all(dir).unwrap();
+        // each frame shows the sheet before the next instruction, the last one the folded sheet
+        let mut page = page;
+        for idx in 0..=instructions.len() {
+            let next = instructions.get(idx).filter(|_| overlay);
+            let (name, frame) = if pbm {
+                (format!("frame_{:03}.pbm", idx), page.pbm(next))
+            } else {
+                (format!("frame_{:03}.txt", idx), page.text(next))
+            };
+            std::fs::write(dir.join(name), frame).unwrap();
+            if let Some(fold) = instructions.get(idx) {
+                let (folded, merged) = page.fold_merged(fold);
+                println!(
+                    "fold {} along {}: {} dots, {} merged",
+                    idx + 1,
+                    fold,
+                    folded.len(),
+                    merged
+                );
+                page = folded;
+            }
+        }
+        return;
+    }
 
     // part 1
-    let mut count: usize = 0;
-    let newpage = fold_count(&page, &instructions[0], &mut count);
-    print_page(&newpage);
-    println!("{}", count);
+    println!("{}", page.fold(&instructions[0]).len());
 
     // part 2
-    println!("dimensions: {} {}", page[0].len(), page.len());
-    let newpage = instructions.iter().fold(page, |acc, elem| fold(&acc, elem));
-    print_page(&newpage);
+    let newpage = instructions.iter().fold(page, |acc, elem| acc.fold(elem));
+    let (code, unknown) = newpage.read();
+    println!("{}", code);
+    if !unknown.is_empty() {
+        unknown
+            .iter()
+            .for_each(|(idx, bitmap)| eprintln!("unknown glyph {}:\n{}", idx + 1, bitmap));
+        print!("{}", newpage);
+    }
 }
 
-fn create_page(page: &[(usize, usize)]) -> Vec<Vec<bool>> {
-    let max = page.iter().fold((0, 0), |acc, elem| {
-        (usize::max(acc.0, elem.0), usize::max(acc.1, elem.1))
-    });
-    let mut result: Vec<Vec<bool>> = vec![vec![false; max.0 + 1]; max.1 + 1];
-    page.iter().for_each(|(x, y)| {
-        result[*y][*x] = true;
-    });
-    result
+/// Glyphs of the block letter font with their rows, each glyph is 4 columns wide and 6 rows high.
+const FONT: &[(char, [&str; 6])] = &[
+    ('A', [".##.", "#..#", "#..#", "####", "#..#", "#..#"]),
+    ('B', ["###.", "#..#", "###.", "#..#", "#..#", "###."]),
+    ('C', [".##.", "#..#", "#...", "#...", "#..#", ".##."]),
+    ('E', ["####", "#...", "###.", "#...", "#...", "####"]),
+    ('F', ["####", "#...", "###.", "#...", "#...", "#..."]),
+    ('G', [".##.", "#..#", "#...", "#.##", "#..#", ".###"]),
+    ('H', ["#..#", "#..#", "####", "#..#", "#..#", "#..#"]),
+    ('J', ["..##", "...#", "...#", "...#", "#..#", ".##."]),
+    ('K', ["#..#", "#.#.", "##..", "#.#.", "#.#.", "#..#"]),
+    ('L', ["#...", "#...", "#...", "#...", "#...", "####"]),
+    ('O', [".##.", "#..#", "#..#", "#..#", "#..#", ".##."]),
+    ('P', ["###.", "#..#", "#..#", "###.", "#...", "#..."]),
+    ('R', ["###.", "#..#", "#..#", "###.", "#.#.", "#..#"]),
+    ('S', [".###", "#...", "#...", ".##.", "...#", "###."]),
+    ('U', ["#..#", "#..#", "#..#", "#..#", "#..#", ".##."]),
+    ('Z', ["####", "...#", "..#.", ".#..", "#...", "####"]),
+];
+
+type Dot = (i64, i64);
+
+/// A transparent sheet, which only stores the positions of its dots.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+struct Page {
+    dots: HashSet<Dot>,
 }
 
-fn fold_count(page: &[Vec<bool>], fold: &Fold, count: &mut usize) -> Vec<Vec<bool>> {
-    let mut result;
-    match fold.axis {
-        Axis::X => {
-            result = Vec::new();
-            page.iter().for_each(|line| {
-                let mut new_line: Vec<bool> = Vec::new();
-                for (e1, e2) in line[..fold.pos]
-                    .iter()
-                    .zip(line[fold.pos + 1..].iter().rev())
-                {
-                    new_line.push(*e1 || *e2);
-                    if *e1 || *e2 {
-                        *count += 1
-                    }
-                }
-                result.push(new_line);
-            })
+impl Page {
+    fn new(dots: &[Dot]) -> Self {
+        Self {
+            dots: dots.iter().cloned().collect(),
         }
-        Axis::Y => {
-            result = Vec::new();
-
-            for (v1, v2) in page[..fold.pos]
-                .iter()
-                .zip(page[fold.pos + 1..].iter().rev())
-            {
-                let mut line: Vec<bool> = Vec::new();
-                for (e1, e2) in v1.iter().zip(v2.iter()) {
-                    line.push(*e1 || *e2);
-                    if *e1 || *e2 {
-                        *count += 1
-                    }
-                }
-                result.push(line)
+    }
+
+    /// number of visible dots
+    fn len(&self) -> usize {
+        self.dots.len()
+    }
+
+    /// Mirrors all dots behind the fold line onto the front part. Dots on the fold line vanish.
+    /// If the folded part is the larger one, its dots end up at negative coordinates.
+    fn fold(&self, fold: &Fold) -> Self {
+        self.fold_merged(fold).0
+    }
+
+    /// Folds like [Page::fold] and also returns how many dots merged with a dot on the front part.
+    fn fold_merged(&self, fold: &Fold) -> (Self, usize) {
+        let mirror = |val: i64| {
+            if val > fold.pos {
+                2 * fold.pos - val
+            } else {
+                val
             }
-        }
+        };
+        let remaining: Vec<Dot> = self
+            .dots
+            .iter()
+            .filter(|(x, y)| match fold.axis {
+                Axis::X => *x != fold.pos,
+                Axis::Y => *y != fold.pos,
+            })
+            .map(|(x, y)| match fold.axis {
+                Axis::X => (mirror(*x), *y),
+                Axis::Y => (*x, mirror(*y)),
+            })
+            .collect();
+        let page = Self {
+            dots: remaining.iter().cloned().collect(),
+        };
+        let merged = remaining.len() - page.len();
+        (page, merged)
+    }
+
+    /// The smallest and largest coordinates of all dots.
+    fn bounds(&self) -> Option<(Dot, Dot)> {
+        let min_x = self.dots.iter().map(|dot| dot.0).min()?;
+        let min_y = self.dots.iter().map(|dot| dot.1).min()?;
+        let max_x = self.dots.iter().map(|dot| dot.0).max()?;
+        let max_y = self.dots.iter().map(|dot| dot.1).max()?;
+        Some(((min_x, min_y), (max_x, max_y)))
     }
-    result
 }
 
-fn fold(page: &[Vec<bool>], fold: &Fold) -> Vec<Vec<bool>> {
-    let mut result;
-    match fold.axis {
-        Axis::X => {
-            result = Vec::new();
-            page.iter().for_each(|line| {
-                let mut new_line: Vec<bool> = Vec::new();
-                if fold.pos * 2 >= line.len() {}
-                for (e1, e2) in line[..fold.pos]
-                    .iter()
-                    .zip(line[fold.pos + 1..].iter().rev())
-                {
-                    new_line.push(*e1 || *e2);
-                }
-                result.push(new_line);
-            })
-        }
-        Axis::Y => {
-            result = Vec::new();
-            let is_even = page.len() % 2 == 0;
-
-            if is_even || (page.len() - 1) / 2 != fold.pos {
-                let mut iter_1 = page[..fold.pos].iter();
-                let mut iter_2 = page[fold.pos + 1..].iter();
-
-                loop {
-                    let v1 = iter_1.next();
-                    let v2 = iter_2.next();
-                    let mut line: Vec<bool> = Vec::new();
-                    if let (Some(v1), Some(v2)) = (v1, v2) {
-                        for (e1, e2) in v1.iter().zip(v2.iter()) {
-                            line.push(*e1 || *e2);
-                        }
-                        result.push(line);
-                    } else if let Some(v1) = v1 {
-                        for e in v1 {
-                            line.push(*e);
-                        }
-                        result.push(line);
-                    } else if let Some(v2) = v2 {
-                        for e in v2 {
-                            line.push(*e);
-                        }
-                        result.insert(0, line);
-                    } else {
-                        break;
-                    }
-                }
-            } else {
-                for (v1, v2) in page[..fold.pos]
-                    .iter()
-                    .zip(page[fold.pos + 1..].iter().rev())
-                {
-                    let mut line: Vec<bool> = Vec::new();
-                    for (e1, e2) in v1.iter().zip(v2.iter()) {
-                        line.push(*e1 || *e2);
-                    }
-                    result.push(line)
+impl Page {
+    /// Reads the dots as a line of [FONT] glyphs, which are separated by an empty column.
+    /// Unknown glyphs are read as `?` and returned with their index and bitmap.
+    fn read(&self) -> (String, Vec<(usize, String)>) {
+        let mut code = String::new();
+        let mut unknown = Vec::new();
+        let ((min_x, min_y), (max_x, _)) = match self.bounds() {
+            Some(bounds) => bounds,
+            None => return (code, unknown),
+        };
+        for idx in 0..(max_x - min_x + 2) / 5 {
+            let rows: Vec<String> = (0..6)
+                .map(|y| {
+                    (0..4)
+                        .map(|x| {
+                            if self.dots.contains(&(min_x + 5 * idx + x, min_y + y)) {
+                                '#'
+                            } else {
+                                '.'
+                            }
+                        })
+                        .collect()
+                })
+                .collect();
+            match FONT.iter().find(|(_, glyph)| glyph[..] == rows[..]) {
+                Some((letter, _)) => code.push(*letter),
+                None => {
+                    code.push('?');
+                    unknown.push((idx as usize, rows.join("\n")));
                 }
             }
         }
+        (code, unknown)
     }
-    println!("newsize {} {}", result[0].len(), result.len());
-    result
 }
 
-fn print_page(page: &[Vec<bool>]) {
-    for line in page {
-        for elem in line {
-            if *elem {
-                print!("#");
-            } else {
-                print!(".");
+impl Page {
+    /// Rows of the sheet with `#` for dots and `.` for empty positions. The line of the `overlay`
+    /// fold is marked as `|` or `-` wherever there is no dot.
+    fn rows(&self, overlay: Option<&Fold>) -> Vec<Vec<char>> {
+        let ((mut min_x, mut min_y), (mut max_x, mut max_y)) = match self.bounds() {
+            Some(bounds) => bounds,
+            None => return Vec::new(),
+        };
+        match overlay {
+            Some(Fold { axis: Axis::X, pos }) => {
+                min_x = std::cmp::min(min_x, *pos);
+                max_x = std::cmp::max(max_x, *pos);
             }
+            Some(Fold { axis: Axis::Y, pos }) => {
+                min_y = std::cmp::min(min_y, *pos);
+                max_y = std::cmp::max(max_y, *pos);
+            }
+            None => (),
         }
-        println!();
+        (min_y..=max_y)
+            .map(|y| {
+                (min_x..=max_x)
+                    .map(|x| match overlay {
+                        _ if self.dots.contains(&(x, y)) => '#',
+                        Some(Fold { axis: Axis::X, pos }) if x == *pos => '|',
+                        Some(Fold { axis: Axis::Y, pos }) if y == *pos => '-',
+                        _ => '.',
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    fn text(&self, overlay: Option<&Fold>) -> String {
+        self.rows(overlay)
+            .iter()
+            .map(|row| row.iter().collect::<String>() + "\n")
+            .collect()
+    }
+
+    /// The sheet as plain PBM image, where the fold line of the `overlay` is dashed.
+    fn pbm(&self, overlay: Option<&Fold>) -> String {
+        let rows = self.rows(overlay);
+        let width = rows.first().map(Vec::len).unwrap_or(0);
+        let pixels: String = rows
+            .iter()
+            .enumerate()
+            .map(|(y, row)| {
+                row.iter()
+                    .enumerate()
+                    .map(|(x, elem)| match elem {
+                        '#' => "1",
+                        '|' | '-' if (x + y) % 2 == 0 => "1",
+                        _ => "0",
+                    })
+                    .collect::<Vec<_>>()
+                    .join(" ")
+                    + "\n"
+            })
+            .collect();
+        format!("P1\n{} {}\n{}", width, rows.len(), pixels)
+    }
+}
+
+impl std::fmt::Display for Page {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.text(None))
     }
 }
 
@@ -174,34 +260,43 @@ enum Axis {
 #[derive(Debug)]
 struct Fold {
     axis: Axis,
-    pos: usize,
+    pos: i64,
+}
+
+impl std::fmt::Display for Fold {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.axis {
+            Axis::X => write!(f, "x={}", self.pos),
+            Axis::Y => write!(f, "y={}", self.pos),
+        }
+    }
 }
 
 struct Parser {}
 impl Parser {
-    fn parse(input: &str) -> IResult<&str, (Vec<(usize, usize)>, Vec<Fold>)> {
+    fn parse(input: &str) -> IResult<&str, (Vec<Dot>, Vec<Fold>)> {
         tuple((
             terminated(Parser::page_description, line_ending),
             many1(terminated(Parser::fold_operation, line_ending)),
         ))(input)
     }
 
-    fn decimal_value(input: &str) -> IResult<&str, usize> {
-        map_res(Parser::decimal, |val| val.parse::<usize>())(input)
+    fn decimal_value(input: &str) -> IResult<&str, i64> {
+        map_res(Parser::decimal, |val| val.parse::<i64>())(input)
     }
 
     fn decimal(input: &str) -> IResult<&str, &str> {
         recognize(many1(one_of("0123456789")))(input)
     }
 
-    fn pair(input: &str) -> IResult<&str, (usize, usize)> {
+    fn pair(input: &str) -> IResult<&str, Dot> {
         tuple((
             Parser::decimal_value,
             preceded(tag(","), Parser::decimal_value),
         ))(input)
     }
 
-    fn page_description(input: &str) -> IResult<&str, Vec<(usize, usize)>> {
+    fn page_description(input: &str) -> IResult<&str, Vec<Dot>> {
         many1(terminated(Parser::pair, line_ending))(input)
     }
 
@@ -213,16 +308,112 @@ impl Parser {
         map_res(
             tuple((alpha1, preceded(tag("="), Parser::decimal_value))),
             |(s, d)| -> Result<Fold, ()> {
-                println!("{}", s);
                 Ok(Fold {
                     pos: d,
                     axis: match s {
                         "y" => Axis::Y,
                         "x" => Axis::X,
-                        _ => unreachable!(),
+                        _ => return Err(()),
                     },
                 })
             },
         )(input)
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const EXAMPLE: &str = include_str!("../exa.txt");
+
+    #[test]
+    fn example() {
+        let (dots, instructions) = Parser::parse(EXAMPLE).unwrap().1;
+        let page = Page::new(&dots);
+        assert_eq!(page.len(), 18);
+        let page = page.fold(&instructions[0]);
+        assert_eq!(page.len(), 17);
+        let page = page.fold(&instructions[1]);
+        assert_eq!(page.len(), 16);
+        assert_eq!(page.to_string(), "#####\n#...#\n#...#\n#...#\n#####\n");
+    }
+
+    #[test]
+    fn uneven_folds() {
+        let page = Page::new(&[(0, 0), (1, 0), (5, 0), (2, 1)]);
+        // the right part is longer than the left one
+        let folded = page.fold(&Fold {
+            axis: Axis::X,
+            pos: 1,
+        });
+        assert_eq!(folded.dots, HashSet::from([(0, 0), (-3, 0), (0, 1)]));
+        assert_eq!(folded.to_string(), "#..#\n...#\n");
+
+        let huge = Page::new(&[(3_000_000, 1_000_000), (1_000_000, 1_000_000)]);
+        let folded = huge.fold(&Fold {
+            axis: Axis::X,
+            pos: 2_000_000,
+        });
+        assert_eq!(folded.len(), 1);
+        assert!(Parser::fold("z=3").is_err());
+    }
+
+    #[test]
+    fn ocr() {
+        let (dots, instructions) = Parser::parse(include_str!("../challenge.txt")).unwrap().1;
+        let page = instructions
+            .iter()
+            .fold(Page::new(&dots), |acc, elem| acc.fold(elem));
+        assert_eq!(page.read(), (String::from("UEFZCUCJ"), Vec::new()));
+
+        let (dots, instructions) = Parser::parse(EXAMPLE).unwrap().1;
+        let page = instructions
+            .iter()
+            .fold(Page::new(&dots), |acc, elem| acc.fold(elem));
+        assert_eq!(
+            page.read(),
+            (
+                String::from("?"),
+                vec![(0, String::from("####\n#...\n#...\n#...\n####\n...."))]
+            )
+        );
+
+        let letters: Vec<Dot> = FONT
+            .iter()
+            .enumerate()
+            .flat_map(|(idx, (_, glyph))| {
+                glyph.iter().enumerate().flat_map(move |(y, row)| {
+                    row.chars()
+                        .enumerate()
+                        .filter(|(_, c)| *c == '#')
+                        .map(move |(x, _)| (5 * idx as i64 + x as i64, y as i64))
+                })
+            })
+            .collect();
+        assert_eq!(Page::new(&letters).read().0, "ABCEFGHJKLOPRSUZ");
+    }
+
+    #[test]
+    fn frames() {
+        let page = Page::new(&[(0, 0), (2, 0), (4, 0), (1, 2)]);
+        let fold = Fold {
+            axis: Axis::X,
+            pos: 3,
+        };
+        assert_eq!(page.text(Some(&fold)), "#.#|#\n...|.\n.#.|.\n");
+        assert_eq!(
+            page.pbm(Some(&fold)),
+            "P1\n5 3\n1 0 1 0 1\n0 0 0 1 0\n0 1 0 0 0\n"
+        );
+        let (folded, merged) = page.fold_merged(&fold);
+        assert_eq!((folded.len(), merged), (3, 1));
+        assert_eq!(folded.text(None), "#.#\n...\n.#.\n");
+        let row = Fold {
+            axis: Axis::Y,
+            pos: 4,
+        };
+        assert_eq!(folded.text(Some(&row)), "#.#\n...\n.#.\n...\n---\n");
+        assert_eq!(row.to_string(), "y=4");
+    }
+}
@@ -1,119 +1,466 @@
-use std::{collections::HashMap, io::BufRead};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+use nom::{
+    bytes::complete::tag,
+    character::complete::{satisfy, space0},
+    combinator::all_consuming,
+    multi::many1,
+    sequence::{delimited, pair, separated_pair},
+    IResult,
+};
 
 fn main() {
-    let file = std::fs::File::open(std::env::args().last().unwrap()).unwrap();
-    let mut rules: HashMap<(char, char), char> = HashMap::new();
-    let mut buffer = std::io::BufReader::new(file);
-
-    let mut starting_line = String::new();
-    buffer.read_line(&mut starting_line).unwrap();
-
-    let mut input: Vec<char> = starting_line.chars().collect();
-    input.remove(input.len() - 1);
-    //let last_char = *input.last().unwrap();
-
-    for line in buffer.lines() {
-        let readline = line.unwrap();
-
-        if readline.len() == 7 {
-            rules.insert(
-                (
-                    readline.chars().next().unwrap(),
-                    readline.chars().nth(1).unwrap(),
-                ),
-                readline.chars().nth(6).unwrap(),
-            );
+    let args: Vec<String> = std::env::args().collect();
+    let input = std::fs::read_to_string(args.last().unwrap()).unwrap();
+    let polymer = Polymer::parse(&input).unwrap_or_else(|err| {
+        eprintln!("{}", err);
+        std::process::exit(1);
+    });
+
+    if args.iter().any(|arg| arg == "--analyze") {
+        polymer
+            .uncovered()
+            .iter()
+            .for_each(|(c1, c2)| println!("no rule for reachable pair {}{}", c1, c2));
+        return;
+    }
+
+    let steps: Vec<u64> = match args.iter().position(|arg| arg == "--steps") {
+        Some(pos) => vec![args[pos + 1].parse().unwrap()],
+        None => vec![10, 40],
+    };
+    if let Some(pos) = args.iter().position(|arg| arg == "--csv") {
+        match polymer.trace(*steps.iter().max().unwrap()) {
+            Ok(csv) => std::fs::write(&args[pos + 1], csv).unwrap(),
+            Err(err) => {
+                eprintln!("{}, at most {} steps fit", err, polymer.max_steps());
+                std::process::exit(1);
+            }
+        }
+    }
+    for steps in steps {
+        match polymer.element_counts(steps) {
+            Ok(counts) => println!("{}", spread(&counts)),
+            Err(err) => {
+                eprintln!("{}, at most {} steps fit", err, polymer.max_steps());
+                std::process::exit(1);
+            }
         }
     }
+}
 
-    println!("{:?}", input);
-    println!("{:?}", rules);
-
-    // part 1
-    //let mut result: Vec<char> = input.clone();
-    // for i in 1..11 {
-    //     println!("step {}", i);
-    //     result = result
-    //         .iter()
-    //         .zip(result[1..].iter())
-    //         .fold(Vec::new(), |mut acc, (e1, e2)| {
-    //             if let Some(answer) = rules.get(&(*e1, *e2)) {
-    //                 acc.append(&mut vec![*e1, *answer]);
-    //             }
-    //             acc
-    //         });
-    //     result.push(last_char);
-    // }
-
-    //println!("{}", score(&result));
-    part2(&input, &rules, 10);
-
-    // for i in 11..26 {
-    //     println!("step {}", i);
-    //     result = result
-    //         .iter()
-    //         .zip(result[1..].iter())
-    //         .fold(Vec::new(), |mut acc, (e1, e2)| {
-    //             if let Some(answer) = rules.get(&(*e1, *e2)) {
-    //                 acc.append(&mut vec![*e1, *answer]);
-    //             }
-    //             acc
-    //         });
-    //     result.push(last_char);
-    // }
-
-    part2(&input, &rules, 40);
+/// Difference between the counts of the most and the least common element.
+fn spread(counts: &BTreeMap<char, u128>) -> u128 {
+    counts.values().max().copied().unwrap_or(0) - counts.values().min().copied().unwrap_or(0)
 }
 
-fn score(list: &[char]) -> usize {
-    let mut scorelist: HashMap<char, usize> = HashMap::new();
-    for elem in list {
-        let score = *scorelist.get(elem).unwrap_or(&0);
-        scorelist.insert(*elem, score + 1);
+/// A polymer template together with its pair insertion rules.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Polymer {
+    template: Vec<char>,
+    /// the elements inserted between the two elements of a pair
+    rules: HashMap<(char, char), Vec<char>>,
+}
+
+impl Polymer {
+    fn element(input: &str) -> IResult<&str, char> {
+        satisfy(char::is_alphanumeric)(input)
+    }
+
+    fn template(input: &str) -> IResult<&str, Vec<char>> {
+        all_consuming(delimited(space0, many1(Self::element), space0))(input)
+    }
+
+    /// A rule like `AB -> C`, which may insert several elements like `AB -> XYZ`.
+    fn rule(input: &str) -> IResult<&str, ((char, char), Vec<char>)> {
+        all_consuming(delimited(
+            space0,
+            separated_pair(
+                pair(Self::element, Self::element),
+                delimited(space0, tag("->"), space0),
+                many1(Self::element),
+            ),
+            space0,
+        ))(input)
+    }
+
+    /// Parses the template in the first line and one rule per following non-empty line.
+    fn parse(input: &str) -> Result<Self, String> {
+        let mut lines = input.lines().enumerate();
+        let template = match lines.next() {
+            Some((_, line)) => {
+                Self::template(line)
+                    .map_err(|_| format!("line 1: invalid template '{}'", line))?
+                    .1
+            }
+            None => return Err(String::from("line 1: missing template")),
+        };
+        let mut rules: HashMap<(char, char), Vec<char>> = HashMap::new();
+        let mut defined: HashMap<(char, char), usize> = HashMap::new();
+        for (nr, line) in lines.filter(|(_, line)| !line.trim().is_empty()) {
+            let (pair, insertion) = Self::rule(line)
+                .map_err(|_| {
+                    format!(
+                        "line {}: expected a rule like 'AB -> C', found '{}'",
+                        nr + 1,
+                        line
+                    )
+                })?
+                .1;
+            if let Some(first) = defined.insert(pair, nr + 1) {
+                return Err(format!(
+                    "line {}: pair {}{} already has a rule in line {}",
+                    nr + 1,
+                    pair.0,
+                    pair.1,
+                    first
+                ));
+            }
+            rules.insert(pair, insertion);
+        }
+        Ok(Self { template, rules })
+    }
+
+    /// Pairs, which occur in the template or are produced by the rules.
+    fn reachable(&self) -> BTreeSet<(char, char)> {
+        let mut reachable: BTreeSet<(char, char)> =
+            self.template.windows(2).map(|w| (w[0], w[1])).collect();
+        let mut todo: Vec<(char, char)> = reachable.iter().cloned().collect();
+        while let Some((c1, c2)) = todo.pop() {
+            if let Some(insertion) = self.rules.get(&(c1, c2)) {
+                let chain: Vec<char> = [&[c1], &insertion[..], &[c2]].concat();
+                chain.windows(2).for_each(|w| {
+                    if reachable.insert((w[0], w[1])) {
+                        todo.push((w[0], w[1]));
+                    }
+                });
+            }
+        }
+        reachable
+    }
+
+    /// Reachable pairs without a rule.
+    fn uncovered(&self) -> Vec<(char, char)> {
+        self.reachable()
+            .into_iter()
+            .filter(|elem| !self.rules.contains_key(elem))
+            .collect()
+    }
+
+    fn transitions(&self) -> Transitions {
+        let pairs: Vec<(char, char)> = self.reachable().into_iter().collect();
+        let index: HashMap<(char, char), usize> = pairs
+            .iter()
+            .enumerate()
+            .map(|(idx, pair)| (*pair, idx))
+            .collect();
+        let mut matrix = vec![vec![0; pairs.len()]; pairs.len()];
+        pairs
+            .iter()
+            .enumerate()
+            .for_each(|(idx, (c1, c2))| match self.rules.get(&(*c1, *c2)) {
+                Some(insertion) => {
+                    let chain: Vec<char> = [&[*c1], &insertion[..], &[*c2]].concat();
+                    chain
+                        .windows(2)
+                        .for_each(|w| matrix[index[&(w[0], w[1])]][idx] += 1);
+                }
+                None => matrix[idx][idx] += 1,
+            });
+        Transitions { pairs, matrix }
+    }
+
+    /// Counts of the pairs in the template, indexed like the pairs of the transitions.
+    fn pair_counts(&self, transitions: &Transitions) -> Vec<u128> {
+        transitions
+            .pairs
+            .iter()
+            .map(|pair| {
+                self.template
+                    .windows(2)
+                    .filter(|w| (w[0], w[1]) == *pair)
+                    .count() as u128
+            })
+            .collect()
+    }
+
+    /// Element counts of a polymer with the given pair counts. Each element is the first one of a
+    /// pair, except for the last element of the template, which always stays at the end.
+    fn counts(
+        &self,
+        transitions: &Transitions,
+        pair_counts: &[u128],
+    ) -> Result<BTreeMap<char, u128>, String> {
+        let mut counts: BTreeMap<char, u128> = BTreeMap::new();
+        let last = (*self.template.last().unwrap(), 1);
+        for (elem, count) in transitions
+            .pairs
+            .iter()
+            .zip(pair_counts)
+            .map(|((c1, _), count)| (*c1, *count))
+            .chain([last])
+        {
+            let total = counts.entry(elem).or_insert(0);
+            *total = total.checked_add(count).ok_or_else(overflow)?;
+        }
+        counts.retain(|_, count| *count > 0);
+        Ok(counts)
+    }
+
+    /// Element counts after the given number of steps, computed with a power of the transitions.
+    /// Counts are kept in u128 rather than an arbitrary precision integer, which keeps the crate
+    /// free of a bigint dependency. As a polymer roughly doubles in length each step, this fails
+    /// after about 125 steps for the puzzle inputs, see [Polymer::max_steps].
+    fn element_counts(&self, steps: u64) -> Result<BTreeMap<char, u128>, String> {
+        let transitions = self.transitions();
+        let pair_counts = apply(
+            &power(&transitions.matrix, steps),
+            &self.pair_counts(&transitions),
+        )?;
+        self.counts(&transitions, &pair_counts)
+    }
+
+    /// The highest number of steps whose element counts fit into u128, found by doubling the
+    /// steps until the counts overflow and bisecting between the last two step counts.
+    fn max_steps(&self) -> u64 {
+        let fits = |steps: u64| self.element_counts(steps).is_ok();
+        let mut hi: u64 = 1;
+        while fits(hi) {
+            if hi == u64::MAX {
+                return hi;
+            }
+            hi = hi.saturating_mul(2);
+        }
+        let mut lo = hi / 2;
+        while hi - lo > 1 {
+            let mid = lo + (hi - lo) / 2;
+            if fits(mid) {
+                lo = mid;
+            } else {
+                hi = mid;
+            }
+        }
+        lo
+    }
+
+    /// Statistics of every step up to the given one as CSV, with the count of each element, the
+    /// most and least common element (the first one in alphabetical order on a tie) and the spread.
+    fn trace(&self, steps: u64) -> Result<String, String> {
+        let transitions = self.transitions();
+        let elements: BTreeSet<char> = transitions
+            .pairs
+            .iter()
+            .flat_map(|(c1, c2)| [*c1, *c2])
+            .chain(self.template.iter().cloned())
+            .collect();
+        let mut csv = format!(
+            "step,{},most,least,spread\n",
+            elements
+                .iter()
+                .map(char::to_string)
+                .collect::<Vec<_>>()
+                .join(",")
+        );
+        let mut pair_counts = self.pair_counts(&transitions);
+        for step in 0..=steps {
+            if step > 0 {
+                pair_counts = apply(&transitions.matrix, &pair_counts)?;
+            }
+            let counts = self.counts(&transitions, &pair_counts)?;
+            let most = counts
+                .iter()
+                .rev()
+                .max_by_key(|(_, count)| **count)
+                .unwrap();
+            let least = counts.iter().min_by_key(|(_, count)| **count).unwrap();
+            csv += &format!(
+                "{},{},{},{},{}\n",
+                step,
+                elements
+                    .iter()
+                    .map(|elem| counts.get(elem).unwrap_or(&0).to_string())
+                    .collect::<Vec<_>>()
+                    .join(","),
+                most.0,
+                least.0,
+                spread(&counts)
+            );
+        }
+        Ok(csv)
     }
-    let mut scores: Vec<usize> = scorelist.values().copied().collect();
-    scores.sort_unstable();
-    scores.last().unwrap() - scores.first().unwrap()
 }
 
-fn part2(inp: &[char], rules: &HashMap<(char, char), char>, steps: usize) {
-    let mut input: HashMap<(char, char), usize> = HashMap::new();
-    let mut last = inp[0];
-    inp[1..].iter().for_each(|e| {
-        *input.entry((last, *e)).or_insert(0) += 1;
-        last = *e;
-    });
-    input.insert(('h', inp[0]), 1);
-    input.insert((inp.last().copied().unwrap(), 'h'), 1);
-    let result = (0..steps)
-        .into_iter()
-        .fold(input, |acc, _| growth_sim(acc, rules));
-
-    let mut result_counts: HashMap<char, usize> = HashMap::new();
-    result.into_iter().for_each(|((c1, c2), count)| {
-        *result_counts.entry(c1).or_insert(0) += count;
-    });
+type Matrix = Vec<Vec<u128>>;
+
+/// The pair insertion rules as a linear map on the counts of all reachable pairs.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Transitions {
+    pairs: Vec<(char, char)>,
+    /// `matrix[j][i]` is how often the pair `i` turns into the pair `j` in a single step
+    matrix: Matrix,
+}
+
+fn overflow() -> String {
+    String::from("element counts do not fit into u128")
+}
+
+/// Product of two matrices, where an entry of [u128::MAX] stands for any count at least as large.
+/// Entries of pairs which do not occur may grow beyond u128 without affecting the element counts,
+/// so an overflow is only reported once such an entry is [applied](apply) to an actual count.
+fn multiply(a: &Matrix, b: &Matrix) -> Matrix {
+    (0..a.len())
+        .map(|row| {
+            (0..b.len())
+                .map(|col| {
+                    (0..b.len()).fold(0u128, |acc, idx| {
+                        acc.saturating_add(a[row][idx].saturating_mul(b[idx][col]))
+                    })
+                })
+                .collect()
+        })
+        .collect()
+}
 
-    result_counts.remove(&'h');
-    println!(
-        "{}",
-        result_counts.values().max().copied().unwrap()
-            - result_counts.values().min().copied().unwrap()
-    );
+fn apply(matrix: &Matrix, vector: &[u128]) -> Result<Vec<u128>, String> {
+    matrix
+        .iter()
+        .map(|row| {
+            row.iter().zip(vector).try_fold(0u128, |acc, (val, count)| {
+                if *count == 0 {
+                    return Ok(acc);
+                }
+                (*val != u128::MAX)
+                    .then_some(*val)
+                    .and_then(|val| val.checked_mul(*count))
+                    .and_then(|val| acc.checked_add(val))
+                    .ok_or_else(overflow)
+            })
+        })
+        .collect()
 }
 
-fn growth_sim(
-    input: HashMap<(char, char), usize>,
-    rules: &HashMap<(char, char), char>,
-) -> HashMap<(char, char), usize> {
-    let mut result = HashMap::new();
-    input.into_iter().for_each(|((c1, c2), count)| {
-        if let Some(mid) = rules.get(&(c1, c2)) {
-            *result.entry((c1, *mid)).or_insert(0) += count;
-            *result.entry((*mid, c2)).or_insert(0) += count;
-        } else {
-            *result.entry((c1, c2)).or_insert(0) += count;
+/// Raises the matrix to the given power by repeated squaring, saturating as [multiply].
+fn power(matrix: &Matrix, mut exponent: u64) -> Matrix {
+    let mut result: Matrix = (0..matrix.len())
+        .map(|row| {
+            (0..matrix.len())
+                .map(|col| u128::from(row == col))
+                .collect()
+        })
+        .collect();
+    let mut base = matrix.clone();
+    while exponent > 0 {
+        if exponent & 1 == 1 {
+            result = multiply(&result, &base);
         }
-    });
+        exponent >>= 1;
+        if exponent > 0 {
+            base = multiply(&base, &base);
+        }
+    }
     result
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parse() {
+        let polymer = Polymer::parse(include_str!("../exa.txt")).unwrap();
+        assert_eq!(polymer.template, vec!['N', 'N', 'C', 'B']);
+        assert_eq!(polymer.rules.len(), 16);
+        assert_eq!(polymer.rules[&('C', 'H')], vec!['B']);
+        assert!(polymer.uncovered().is_empty());
+
+        let polymer = Polymer::parse("AB\n\nAB -> XYZ\nXY->A\n").unwrap();
+        assert_eq!(polymer.rules[&('A', 'B')], vec!['X', 'Y', 'Z']);
+        assert_eq!(
+            polymer.uncovered(),
+            vec![('A', 'X'), ('A', 'Y'), ('X', 'A'), ('Y', 'Z'), ('Z', 'B')]
+        );
+    }
+
+    #[test]
+    fn errors() {
+        assert_eq!(
+            Polymer::parse("NNCB\n\nCH -> B\nHH ->\n"),
+            Err(String::from(
+                "line 4: expected a rule like 'AB -> C', found 'HH ->'"
+            ))
+        );
+        assert_eq!(
+            Polymer::parse("NNCB\n\nCH -> B\nCHX -> B\n"),
+            Err(String::from(
+                "line 4: expected a rule like 'AB -> C', found 'CHX -> B'"
+            ))
+        );
+        assert_eq!(
+            Polymer::parse("NNCB\n\nCH -> B\n\nCH -> C\n"),
+            Err(String::from("line 5: pair CH already has a rule in line 3"))
+        );
+        assert_eq!(
+            Polymer::parse("NN CB\n"),
+            Err(String::from("line 1: invalid template 'NN CB'"))
+        );
+    }
+
+    #[test]
+    fn growth() {
+        let polymer = Polymer::parse(include_str!("../exa.txt")).unwrap();
+        assert_eq!(
+            polymer.element_counts(0).unwrap(),
+            BTreeMap::from([('B', 1), ('C', 1), ('N', 2)])
+        );
+        let counts = polymer.element_counts(10).unwrap();
+        assert_eq!(
+            counts,
+            BTreeMap::from([('B', 1749), ('C', 298), ('H', 161), ('N', 865)])
+        );
+        assert_eq!(spread(&counts), 1588);
+        assert_eq!(spread(&polymer.element_counts(40).unwrap()), 2188189693529);
+        assert!(polymer.element_counts(1000).is_err());
+        let max = polymer.max_steps();
+        assert!(polymer.element_counts(max).is_ok());
+        assert!(polymer.element_counts(max + 1).is_err());
+
+        // the pair CC is not in the template, so its entries may overflow
+        let polymer = Polymer::parse("AB\n\nAB -> C\nAC -> C\nCC -> C\n").unwrap();
+        assert_eq!(
+            polymer.element_counts(128).unwrap(),
+            BTreeMap::from([('A', 1), ('B', 1), ('C', 1 << 127)])
+        );
+        assert!(polymer.element_counts(129).is_err());
+        assert_eq!(polymer.max_steps(), 128);
+
+        // the polymer grows by two elements per step
+        let polymer = Polymer::parse("AB\n\nAB -> AB\n").unwrap();
+        assert_eq!(
+            polymer.element_counts(5_000_000_000).unwrap(),
+            BTreeMap::from([('A', 5_000_000_001), ('B', 5_000_000_001)])
+        );
+        let polymer = Polymer::parse("ABA\n\nAB -> XYZ\n").unwrap();
+        assert_eq!(
+            polymer.element_counts(3).unwrap(),
+            BTreeMap::from([('A', 2), ('B', 1), ('X', 1), ('Y', 1), ('Z', 1)])
+        );
+    }
+
+    #[test]
+    fn trace() {
+        let polymer = Polymer::parse(include_str!("../exa.txt")).unwrap();
+        let csv = polymer.trace(10).unwrap();
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines.len(), 12);
+        assert_eq!(lines[0], "step,B,C,H,N,most,least,spread");
+        assert_eq!(lines[1], "0,1,1,0,2,N,B,1");
+        assert_eq!(lines[2], "1,2,2,1,2,B,H,1");
+        assert_eq!(lines[11], "10,1749,298,161,865,B,H,1588");
+
+        let polymer = Polymer::parse("AB\n\nAB -> AB\n").unwrap();
+        assert_eq!(
+            polymer.trace(2).unwrap(),
+            "step,A,B,most,least,spread\n0,1,1,A,A,0\n1,2,2,A,A,0\n2,3,3,A,A,0\n"
+        );
+    }
+}